@@ -0,0 +1,5 @@
+// `chess-uci` plays through the universal chess interface on stdin and stdout so guis and tournament managers
+// can use the engine without the piston window
+fn main() {
+    chess::uci::run();
+}
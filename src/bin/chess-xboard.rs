@@ -0,0 +1,5 @@
+// `chess-xboard` plays through the chess engine communication protocol on stdin and stdout for xboard, winboard
+// and older tournament scripts
+fn main() {
+    chess::xboard::run();
+}
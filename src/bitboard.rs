@@ -0,0 +1,258 @@
+// bitboards are only used for the standard 8*8 board, bit i is the same square as index i of the mailbox
+// so a8 is bit 0 and h1 is bit 63
+use crate::board::*;
+
+// (row, column) steps for each sliding direction, the first four are rook directions and the rest bishop directions
+const DIRECTIONS: [(i32, i32); 8] = [(-1, 0), (1, 0), (0, -1), (0, 1), (-1, -1), (-1, 1), (1, -1), (1, 1)];
+
+const KNIGHT_STEPS: [(i32, i32); 8] = [(-2, -1), (-2, 1), (-1, -2), (-1, 2), (1, -2), (1, 2), (2, -1), (2, 1)];
+const KING_STEPS: [(i32, i32); 8] = [(-1, -1), (-1, 0), (-1, 1), (0, -1), (0, 1), (1, -1), (1, 0), (1, 1)];
+
+// every square reachable with one of the steps from each square
+const fn step_table(steps: &[(i32, i32); 8]) -> [u64; 64] {
+    let mut table = [0; 64];
+    let mut sq = 0;
+    while sq < 64 {
+        let mut i = 0;
+        while i < steps.len() {
+            let row = sq as i32 / 8 + steps[i].0;
+            let col = sq as i32 % 8 + steps[i].1;
+            if row >= 0 && row < 8 && col >= 0 && col < 8 {
+                table[sq] |= 1 << (row * 8 + col);
+            }
+            i += 1;
+        }
+        sq += 1;
+    }
+    table
+}
+
+// the squares a pawn of each color attacks, light pawns attack up the board (towards bit 0)
+const fn pawn_table() -> [[u64; 64]; 2] {
+    let mut table = [[0; 64]; 2];
+    let mut sq = 0;
+    while sq < 64 {
+        let row = sq as i32 / 8;
+        let col = sq as i32 % 8;
+        let mut side = -1;
+        while side <= 1 {
+            if col + side >= 0 && col + side < 8 {
+                if row > 0 {
+                    table[0][sq] |= 1 << ((row - 1) * 8 + col + side);
+                }
+                if row < 7 {
+                    table[1][sq] |= 1 << ((row + 1) * 8 + col + side);
+                }
+            }
+            side += 2;
+        }
+        sq += 1;
+    }
+    table
+}
+
+// the full ray from each square in each direction on an empty board, not including the square itself
+const fn ray_table() -> [[u64; 64]; 8] {
+    let mut table = [[0; 64]; 8];
+    let mut dir = 0;
+    while dir < 8 {
+        let mut sq = 0;
+        while sq < 64 {
+            let mut row = sq as i32 / 8 + DIRECTIONS[dir].0;
+            let mut col = sq as i32 % 8 + DIRECTIONS[dir].1;
+            while row >= 0 && row < 8 && col >= 0 && col < 8 {
+                table[dir][sq] |= 1 << (row * 8 + col);
+                row += DIRECTIONS[dir].0;
+                col += DIRECTIONS[dir].1;
+            }
+            sq += 1;
+        }
+        dir += 1;
+    }
+    table
+}
+
+const KNIGHT_ATTACKS: [u64; 64] = step_table(&KNIGHT_STEPS);
+const KING_ATTACKS: [u64; 64] = step_table(&KING_STEPS);
+const PAWN_ATTACKS: [[u64; 64]; 2] = pawn_table();
+const RAYS: [[u64; 64]; 8] = ray_table();
+
+pub fn knight_attacks(sq: usize) -> u64 {
+    KNIGHT_ATTACKS[sq]
+}
+
+pub fn king_attacks(sq: usize) -> u64 {
+    KING_ATTACKS[sq]
+}
+
+pub fn pawn_attacks(sq: usize, color: Color) -> u64 {
+    PAWN_ATTACKS[color_index(color)][sq]
+}
+
+// classical sliding attacks, the ray is cut off just past the first blocker in that direction
+fn ray_attacks(sq: usize, occupied: u64, dir: usize) -> u64 {
+    let ray = RAYS[dir][sq];
+    let blockers = ray & occupied;
+    if blockers == 0 {
+        return ray;
+    }
+
+    // rays going down the board move towards higher bits so the nearest blocker is the lowest bit, and the other way round
+    let (drow, dcol) = DIRECTIONS[dir];
+    let blocker = match drow > 0 || (drow == 0 && dcol > 0) {
+        true => blockers.trailing_zeros() as usize,
+        false => 63 - blockers.leading_zeros() as usize,
+    };
+    ray ^ RAYS[dir][blocker]
+}
+
+pub fn rook_attacks(sq: usize, occupied: u64) -> u64 {
+    (0..4).fold(0, |acc, dir| acc | ray_attacks(sq, occupied, dir))
+}
+
+pub fn bishop_attacks(sq: usize, occupied: u64) -> u64 {
+    (4..8).fold(0, |acc, dir| acc | ray_attacks(sq, occupied, dir))
+}
+
+pub fn color_index(color: Color) -> usize {
+    match color {
+        Color::Light => 0,
+        Color::Dark => 1,
+    }
+}
+
+pub fn piece_index(piece: PieceType) -> usize {
+    match piece {
+        PieceType::Pawn => 0,
+        PieceType::Knight => 1,
+        PieceType::Bishop => 2,
+        PieceType::Rook => 3,
+        PieceType::Queen => 4,
+        PieceType::King => 5,
+    }
+}
+
+// gives back the square of the lowest set bit and clears it, for walking every square in a bitboard
+pub fn pop_lsb(bb: &mut u64) -> usize {
+    let sq = bb.trailing_zeros() as usize;
+    *bb &= *bb - 1;
+    sq
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Bitboards {
+    pieces: [u64; 6],
+    colors: [u64; 2],
+}
+
+impl Bitboards {
+    pub fn new(squares: &[Option<Piece>]) -> Self {
+        let mut bb = Bitboards {
+            pieces: [0; 6],
+            colors: [0; 2],
+        };
+        for (sq, p) in squares.iter().enumerate() {
+            if let Some(p) = p {
+                bb.toggle(sq, *p);
+            }
+        }
+        bb
+    }
+
+    // adds the piece if it is not on sq and removes it if it is
+    pub fn toggle(&mut self, sq: usize, piece: Piece) {
+        self.pieces[piece_index(piece.get_piecetype())] ^= 1 << sq;
+        self.colors[color_index(piece.get_color())] ^= 1 << sq;
+    }
+
+    pub fn occupied(&self) -> u64 {
+        self.colors[0] | self.colors[1]
+    }
+
+    pub fn color(&self, color: Color) -> u64 {
+        self.colors[color_index(color)]
+    }
+
+    pub fn pieces(&self, piece: PieceType, color: Color) -> u64 {
+        self.pieces[piece_index(piece)] & self.colors[color_index(color)]
+    }
+
+    pub fn is_attacked(&self, sq: usize, by: Color) -> bool {
+        let occupied = self.occupied();
+        let queens = self.pieces(PieceType::Queen, by);
+
+        // a pawn of by attacks sq exactly when a pawn of the other color on sq would attack it back
+        pawn_attacks(sq, by.opposite()) & self.pieces(PieceType::Pawn, by) != 0
+            || knight_attacks(sq) & self.pieces(PieceType::Knight, by) != 0
+            || king_attacks(sq) & self.pieces(PieceType::King, by) != 0
+            || rook_attacks(sq, occupied) & (self.pieces(PieceType::Rook, by) | queens) != 0
+            || bishop_attacks(sq, occupied) & (self.pieces(PieceType::Bishop, by) | queens) != 0
+    }
+
+    // every square the piece on sq could move to while ignoring king safety and castling
+    pub fn targets(&self, sq: usize, piece: Piece, en_passant: Option<usize>) -> u64 {
+        let color = piece.get_color();
+        let own = self.color(color);
+        let occupied = self.occupied();
+
+        match piece.get_piecetype() {
+            PieceType::Pawn => {
+                let empty = !occupied;
+                let (one, start_row) = match color {
+                    Color::Light => ((1u64 << sq) >> 8, 6),
+                    Color::Dark => ((1u64 << sq) << 8, 1),
+                };
+                let one = one & empty;
+                let two = match (sq / 8 == start_row, color) {
+                    (true, Color::Light) => (one >> 8) & empty,
+                    (true, Color::Dark) => (one << 8) & empty,
+                    (false, _) => 0,
+                };
+
+                // the en passant square counts as an enemy piece for the capture mask
+                let mut enemies = self.color(color.opposite());
+                if let Some(ep) = en_passant {
+                    enemies |= 1 << ep;
+                }
+                one | two | (pawn_attacks(sq, color) & enemies)
+            }
+            PieceType::Knight => knight_attacks(sq) & !own,
+            PieceType::Bishop => bishop_attacks(sq, occupied) & !own,
+            PieceType::Rook => rook_attacks(sq, occupied) & !own,
+            PieceType::Queen => (rook_attacks(sq, occupied) | bishop_attacks(sq, occupied)) & !own,
+            PieceType::King => king_attacks(sq) & !own,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn squares(mut bb: u64) -> Vec<usize> {
+        let mut ans = vec![];
+        while bb != 0 {
+            ans.push(pop_lsb(&mut bb));
+        }
+        ans
+    }
+
+    #[test]
+    fn leaper_tables_stay_on_the_board() {
+        // a8 is square 0, its knight can only reach c7 and b6
+        assert_eq!(squares(knight_attacks(0)), vec![10, 17]);
+        // h1 is square 63
+        assert_eq!(squares(king_attacks(63)), vec![54, 55, 62]);
+        assert_eq!(squares(pawn_attacks(48, Color::Light)), vec![41]);
+        assert_eq!(squares(pawn_attacks(15, Color::Dark)), vec![22]);
+    }
+
+    #[test]
+    fn sliders_stop_at_the_first_blocker() {
+        // rook on d4 (35) with blockers on d6 (19) and f4 (37)
+        let occupied = (1 << 19) | (1 << 37);
+        assert_eq!(squares(rook_attacks(35, occupied)), vec![19, 27, 32, 33, 34, 36, 37, 43, 51, 59]);
+        // bishop on a8 with a blocker on c6 (18)
+        assert_eq!(squares(bishop_attacks(0, 1 << 18)), vec![9, 18]);
+    }
+}
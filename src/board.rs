@@ -1,12 +1,25 @@
 use std::collections::HashSet;
 
+use crate::bitboard::*;
+use crate::eval::piece_value;
+use crate::zobrist;
+
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub enum Color {
     Light,
     Dark,
 }
 
-#[derive(Debug, Copy, Clone)]
+impl Color {
+    pub fn opposite(&self) -> Color {
+        match self {
+            Color::Light => Color::Dark,
+            Color::Dark => Color::Light,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
 pub enum PieceType {
     Pawn,
     Knight,
@@ -16,7 +29,13 @@ pub enum PieceType {
     Queen,
 }
 
-#[derive(Debug, Copy, Clone)]
+// the pieces a pawn may turn into when it reaches the last rank
+pub const PROMOTION_PIECES: [PieceType; 4] = [PieceType::Queen, PieceType::Rook, PieceType::Bishop, PieceType::Knight];
+
+// the standard 8*8 starting position
+pub const START_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+#[derive(Debug, Copy, Clone, PartialEq)]
 pub struct Piece {
     piece: PieceType,
     color: Color,
@@ -52,6 +71,26 @@ impl Piece {
         None
     }
 
+    pub fn from_type(piece: PieceType, color: Color) -> Self {
+        Piece { piece, color }
+    }
+
+    // the letter used for this piece in fen strings, upper case for light
+    pub fn get_code(&self) -> char {
+        let c = match self.piece {
+            PieceType::Pawn => 'p',
+            PieceType::Knight => 'n',
+            PieceType::Bishop => 'b',
+            PieceType::Rook => 'r',
+            PieceType::Queen => 'q',
+            PieceType::King => 'k',
+        };
+        match self.color {
+            Color::Light => c.to_ascii_uppercase(),
+            Color::Dark => c,
+        }
+    }
+
     pub fn get_file_location(&self) -> String {
         let mut ans = match self.color {
             Color::Light => "pieces/w".to_string(),
@@ -116,116 +155,946 @@ impl Piece {
     pub fn get_color(&self) -> Color {
         self.color
     }
+}
 
-    #[allow(dead_code)]
-    pub fn get_value(&self) -> i32 {
-        let ans = match self.piece {
-            PieceType::Pawn => 1,
-            PieceType::Knight => 3,
-            PieceType::Bishop => 3,
-            PieceType::Rook => 5,
-            PieceType::Queen => 8,
-            PieceType::King => 10,
-        };
-        match self.color {
-            Color::Light => ans,
-            Color::Dark => -ans,
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum GameStatus {
+    Ongoing,
+    // holds the color that delivered the checkmate
+    Checkmate(Color),
+    Stalemate,
+    Draw(DrawReason),
+}
+
+// the ways a game can be drawn other than stalemate, the first two can only be claimed by a player
+// while the rest end the game on their own
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum DrawReason {
+    ThreefoldRepetition,
+    FiftyMoveRule,
+    FivefoldRepetition,
+    SeventyFiveMoveRule,
+    InsufficientMaterial,
+}
+
+impl std::fmt::Display for DrawReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            DrawReason::ThreefoldRepetition => write!(f, "threefold repetition"),
+            DrawReason::FiftyMoveRule => write!(f, "fifty move rule"),
+            DrawReason::FivefoldRepetition => write!(f, "fivefold repetition"),
+            DrawReason::SeventyFiveMoveRule => write!(f, "seventy five move rule"),
+            DrawReason::InsufficientMaterial => write!(f, "insufficient material"),
+        }
+    }
+}
+
+impl std::fmt::Display for GameStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            GameStatus::Ongoing => write!(f, "game in progress"),
+            GameStatus::Checkmate(Color::Light) => write!(f, "checkmate, light wins"),
+            GameStatus::Checkmate(Color::Dark) => write!(f, "checkmate, dark wins"),
+            GameStatus::Stalemate => write!(f, "stalemate, draw"),
+            GameStatus::Draw(reason) => write!(f, "draw by {}", reason),
+        }
+    }
+}
+
+// everything that can be wrong with a fen string, column is the 0 based byte offset into the string where the problem is
+#[derive(Debug, Clone, PartialEq)]
+pub enum FenError {
+    BadPiece { column: usize, found: char },
+    RankTooLong { column: usize, rank: usize },
+    RankTooShort { column: usize, rank: usize },
+    WrongRankCount { column: usize, expected: usize },
+    MissingKing(Color),
+    TooManyKings(Color),
+    PawnOnBackRank { column: usize },
+    BadSideToMove { column: usize },
+    BadCastling { column: usize, found: char },
+    BadEnPassant { column: usize },
+    BadClock { column: usize },
+    TooManyFields { column: usize },
+}
+
+impl std::fmt::Display for FenError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            FenError::BadPiece { column, found } => write!(f, "unknown piece letter '{}' at column {}", found, column),
+            FenError::RankTooLong { column, rank } => write!(f, "rank {} has too many squares at column {}", rank + 1, column),
+            FenError::RankTooShort { column, rank } => write!(f, "rank {} has too few squares at column {}", rank + 1, column),
+            FenError::WrongRankCount { column, expected } => write!(f, "expected {} ranks, ran out at column {}", expected, column),
+            FenError::MissingKing(color) => write!(f, "{:?} has no king", color),
+            FenError::TooManyKings(color) => write!(f, "{:?} has more than one king", color),
+            FenError::PawnOnBackRank { column } => write!(f, "pawn on the first or last rank at column {}", column),
+            FenError::BadSideToMove { column } => write!(f, "side to move must be w or b at column {}", column),
+            FenError::BadCastling { column, found } => write!(f, "invalid castling right '{}' at column {}", found, column),
+            FenError::BadEnPassant { column } => write!(f, "invalid en passant square at column {}", column),
+            FenError::BadClock { column } => write!(f, "invalid move clock at column {}", column),
+            FenError::TooManyFields { column } => write!(f, "unexpected extra field at column {}", column),
+        }
+    }
+}
+
+impl std::error::Error for FenError {}
+
+// whether each side may still castle towards the king side (right) or queen side (left) rook
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct CastlingRights {
+    pub light_king_side: bool,
+    pub light_queen_side: bool,
+    pub dark_king_side: bool,
+    pub dark_queen_side: bool,
+}
+
+impl CastlingRights {
+    pub fn none() -> Self {
+        CastlingRights {
+            light_king_side: false,
+            light_queen_side: false,
+            dark_king_side: false,
+            dark_queen_side: false,
+        }
+    }
+
+    pub fn all() -> Self {
+        CastlingRights {
+            light_king_side: true,
+            light_queen_side: true,
+            dark_king_side: true,
+            dark_queen_side: true,
         }
     }
+
+    pub fn get(&self, color: Color, king_side: bool) -> bool {
+        match (color, king_side) {
+            (Color::Light, true) => self.light_king_side,
+            (Color::Light, false) => self.light_queen_side,
+            (Color::Dark, true) => self.dark_king_side,
+            (Color::Dark, false) => self.dark_queen_side,
+        }
+    }
+
+    pub fn add(&mut self, color: Color, king_side: bool) {
+        match (color, king_side) {
+            (Color::Light, true) => self.light_king_side = true,
+            (Color::Light, false) => self.light_queen_side = true,
+            (Color::Dark, true) => self.dark_king_side = true,
+            (Color::Dark, false) => self.dark_queen_side = true,
+        }
+    }
+
+    pub fn remove(&mut self, color: Color, king_side: bool) {
+        match (color, king_side) {
+            (Color::Light, true) => self.light_king_side = false,
+            (Color::Light, false) => self.light_queen_side = false,
+            (Color::Dark, true) => self.dark_king_side = false,
+            (Color::Dark, false) => self.dark_queen_side = false,
+        }
+    }
+}
+
+// a single move, the flags say what kind of special move it is so make_move does not have to work it out again
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Move {
+    pub from: usize,
+    pub to: usize,
+    pub flags: u8,
+    pub promotion: Option<PieceType>,
+}
+
+impl Move {
+    pub const QUIET: u8 = 0;
+    pub const CAPTURE: u8 = 1;
+    pub const DOUBLE_PUSH: u8 = 2;
+    pub const EN_PASSANT: u8 = 4;
+    pub const CASTLE: u8 = 8;
+    pub const PROMOTION: u8 = 16;
+
+    pub fn has(&self, flag: u8) -> bool {
+        self.flags & flag != 0
+    }
+}
+
+// everything needed to take a move back
+#[derive(Debug, Copy, Clone)]
+struct MoveRecord {
+    mv: Move,
+    moved: Piece,
+    captured: Option<Piece>,
+    // only differs from to when the capture was en passant
+    captured_pos: usize,
+    castling: CastlingRights,
+    en_passant: Option<usize>,
+    halfmove_clock: u32,
+    fullmove_number: u32,
+    hash: u64,
 }
 
 #[derive(Debug, Clone)]
 pub struct Board {
     pieces: Vec<Option<Piece>>,
     size: i32,
+    castling: CastlingRights,
+    // the square a pawn skipped over with its last double step if it can be taken en passant
+    en_passant: Option<usize>,
+    turn: Color,
+    // moves since the last capture or pawn move, used for the fifty move rule
+    halfmove_clock: u32,
+    // starts at 1 and goes up after every dark move
+    fullmove_number: u32,
+    history: Vec<MoveRecord>,
+    // moves that were taken back and can be played again until a different move is made
+    redo: Vec<Move>,
+    // kept in sync with pieces on 8*8 boards to speed up move generation, other sizes only use the mailbox
+    bitboards: Option<Bitboards>,
+    // zobrist hash of the position, updated as moves are made and taken back
+    hash: u64,
 }
 
 impl Board {
-    pub fn new(fen: String, s: i32) -> Self {
+    // reads all six fen fields, anything after the piece placement that is missing falls back to a fresh game
+    pub fn from_fen(fen: &str, size: i32) -> Result<Self, FenError> {
+        Self::parse_fen(fen, size, true)
+    }
+
+    // same as from_fen but skips the checks that the position is playable (kings, pawns on the back rank, castling rooks)
+    // so boards that are only meant to be looked at like the n queens solutions can still be loaded
+    pub fn diagram_from_fen(fen: &str, size: i32) -> Result<Self, FenError> {
+        Self::parse_fen(fen, size, false)
+    }
+
+    fn parse_fen(fen: &str, size: i32, validate: bool) -> Result<Self, FenError> {
+        let s = size.max(0) as usize;
         let mut board = vec![];
 
+        // each field keeps the column it starts at so errors can point back into the fen string
+        let mut fields = vec![];
+        let mut start = None;
+        for (column, c) in fen.char_indices().chain(std::iter::once((fen.len(), ' '))) {
+            match (c.is_whitespace(), start) {
+                (true, Some(st)) => {
+                    fields.push((st, &fen[st..column]));
+                    start = None;
+                }
+                (false, None) => start = Some(column),
+                _ => (),
+            }
+        }
+        let mut fields = fields.into_iter();
+        let (placement_start, placement) = fields.next().unwrap_or((0, ""));
+
+        let mut rank = 0;
         let mut col = 0;
-        let mut iterator = fen.chars();
-        while let Some(mut i) = iterator.next() {
-            if i.is_ascii_digit() {
-                let mut num = String::new();
-                num.push(i);
-
-                #[allow(clippy::while_let_on_iterator)]
-                while let Some(c) = iterator.next() {
-                    i = c;
-                    if c.is_ascii_digit() {
-                        num.push(c);
-                    }
-                    else {
-                        break;
+        let mut run: Option<usize> = None;
+        let mut pawn_columns = vec![];
+        // the character after the last one is used as the end of the final rank
+        for (offset, c) in placement.char_indices().chain(std::iter::once((placement.len(), '/'))) {
+            let column = placement_start + offset;
+
+            // runs of empty squares can be more than one digit long on boards bigger than 9 files
+            if let Some(d) = c.to_digit(10) {
+                let count = run.unwrap_or(0).saturating_mul(10).saturating_add(d as usize);
+                if col + count > s {
+                    return Err(FenError::RankTooLong { column, rank });
+                }
+                run = Some(count);
+                continue;
+            }
+            if let Some(count) = run.take() {
+                board.resize(board.len() + count, None);
+                col += count;
+            }
+
+            if c == '/' {
+                // an empty rank like the ones in rnbqkbnr/pppppppp/////PPPPPPPP/RNBQKBNR is short for a rank with no pieces
+                if col == 0 {
+                    board.resize(board.len() + s, None);
+                    col = s;
+                }
+                if col < s {
+                    return Err(FenError::RankTooShort { column, rank });
+                }
+                if rank >= s {
+                    return Err(FenError::WrongRankCount { column, expected: s });
+                }
+                rank += 1;
+                col = 0;
+                continue;
+            }
+
+            let piece = match Piece::new(c) {
+                Some(p) => p,
+                None => return Err(FenError::BadPiece { column, found: c }),
+            };
+            if col >= s {
+                return Err(FenError::RankTooLong { column, rank });
+            }
+            if piece.get_piecetype() == PieceType::Pawn && (rank == 0 || rank == s - 1) {
+                pawn_columns.push(column);
+            }
+            board.push(Some(piece));
+            col += 1;
+        }
+        if rank != s {
+            return Err(FenError::WrongRankCount { column: placement_start + placement.len(), expected: s });
+        }
+
+        let mut b = Board {
+            pieces: board,
+            size,
+            castling: CastlingRights::all(),
+            en_passant: None,
+            turn: Color::Light,
+            halfmove_clock: 0,
+            fullmove_number: 1,
+            history: vec![],
+            redo: vec![],
+            bitboards: None,
+            hash: 0,
+        };
+        if size == 8 {
+            b.bitboards = Some(Bitboards::new(&b.pieces));
+        }
+
+        if validate {
+            if let Some(column) = pawn_columns.first() {
+                return Err(FenError::PawnOnBackRank { column: *column });
+            }
+            for color in [Color::Light, Color::Dark] {
+                let kings = b.pieces.iter().filter(|p| **p == Some(Piece::from_type(PieceType::King, color))).count();
+                match kings {
+                    0 => return Err(FenError::MissingKing(color)),
+                    1 => (),
+                    _ => return Err(FenError::TooManyKings(color)),
+                }
+            }
+        }
+
+        match fields.next() {
+            Some((_, "w")) | None => b.turn = Color::Light,
+            Some((_, "b")) => b.turn = Color::Dark,
+            Some((column, _)) => return Err(FenError::BadSideToMove { column }),
+        }
+
+        // without a castling field a side keeps its rights as long as its king and rooks are on their starting squares
+        if let Some((column, rights)) = fields.next() {
+            b.castling = CastlingRights::none();
+            if rights != "-" {
+                for (offset, c) in rights.char_indices() {
+                    let (color, king_side) = match c {
+                        'K' => (Color::Light, true),
+                        'Q' => (Color::Light, false),
+                        'k' => (Color::Dark, true),
+                        'q' => (Color::Dark, false),
+                        _ => return Err(FenError::BadCastling { column: column + offset, found: c }),
+                    };
+                    // each right may only be given once and only if that king and rook are still at home
+                    if b.castling.get(color, king_side) || (validate && !b.can_ever_castle(color, king_side)) {
+                        return Err(FenError::BadCastling { column: column + offset, found: c });
                     }
+                    b.castling.add(color, king_side);
                 }
-                
-                let count = num.parse::<i32>().unwrap();
-                
-                // let count = match i.to_digit(10) {
-                //     Some(val) => val as i32-1,
-                //     None => 0,
-                // };
-                //row += count;
-                for _j in 0..count {
-                    col += 1;
-                    board.push(Piece::new('#'));
+            }
+        }
+        for color in [Color::Light, Color::Dark] {
+            for king_side in [true, false] {
+                if !b.can_ever_castle(color, king_side) {
+                    b.castling.remove(color, king_side);
                 }
             }
-            if i == '/' {
-                if col < s {
-                    for _j in col..s {
-                        board.push(Piece::new('#'));
+        }
+
+        // the en passant square always sits right behind the pawn of the side that just moved, with that pawn one
+        // square further on and the square it started from still empty
+        if let Some((column, square)) = fields.next() {
+            if square != "-" {
+                let (behind_row, forward) = match b.turn {
+                    Color::Light => (2, 1),
+                    Color::Dark => (size - 3, -1),
+                };
+                let pawn = Some(Piece::from_type(PieceType::Pawn, b.turn.opposite()));
+                // squares off the edge of a small board count as wrong rather than empty
+                let at = |sq: usize, dr: i32| b.offset(sq as i32, dr, 0).map(|v| b.pieces[v as usize]);
+                match b.square_from_algebraic(square) {
+                    Some(sq) if sq as i32 / size == behind_row
+                        && b.pieces[sq].is_none()
+                        && at(sq, forward) == Some(pawn)
+                        && at(sq, -forward) == Some(None) =>
+                    {
+                        b.en_passant = Some(sq)
                     }
+                    _ => return Err(FenError::BadEnPassant { column }),
                 }
-                col = 0;
             }
-            else if !i.is_ascii_digit() {
-                col += 1;
-                board.push(Piece::new(i));
+        }
+
+        if let Some((column, v)) = fields.next() {
+            b.halfmove_clock = v.parse::<u32>().map_err(|_| FenError::BadClock { column })?;
+        }
+        // the fullmove number starts at 1
+        if let Some((column, v)) = fields.next() {
+            b.fullmove_number = match v.parse::<u32>() {
+                Ok(n) if n >= 1 => n,
+                _ => return Err(FenError::BadClock { column }),
+            };
+        }
+        if let Some((column, _)) = fields.next() {
+            return Err(FenError::TooManyFields { column });
+        }
+        b.hash = b.compute_hash();
+        Ok(b)
+    }
+
+    // writes the board back out as a full six field fen string
+    pub fn to_fen(&self) -> String {
+        let mut fen = String::new();
+
+        for row in 0..self.size {
+            let mut empty = 0;
+            for col in 0..self.size {
+                match self.pieces[(row * self.size + col) as usize] {
+                    Some(p) => {
+                        // the whole run is written as one number, parse_fen reads it back the same way
+                        if empty != 0 {
+                            fen += empty.to_string().as_str();
+                            empty = 0;
+                        }
+                        fen.push(p.get_code());
+                    }
+                    None => empty += 1,
+                }
+            }
+            if empty != 0 {
+                fen += empty.to_string().as_str();
+            }
+            if row != self.size - 1 {
+                fen.push('/');
             }
         }
 
-        let c = (s * s) as usize;
-        while board.len() < c {
-            board.push(Piece::new('#'));
+        fen += match self.turn {
+            Color::Light => " w ",
+            Color::Dark => " b ",
+        };
+
+        let rights = [
+            (self.castling.light_king_side, 'K'),
+            (self.castling.light_queen_side, 'Q'),
+            (self.castling.dark_king_side, 'k'),
+            (self.castling.dark_queen_side, 'q'),
+        ];
+        let castling: String = rights.iter().filter(|(has, _)| *has).map(|(_, c)| *c).collect();
+        fen += match castling.is_empty() {
+            true => "-",
+            false => castling.as_str(),
+        };
+
+        let en_passant = match self.en_passant {
+            Some(sq) => self.square_to_algebraic(sq),
+            None => "-".to_string(),
+        };
+        fen += format!(" {} {} {}", en_passant, self.halfmove_clock, self.fullmove_number).as_str();
+        fen
+    }
+
+    // writes a move the way uci does it like e2e4 or e7e8q
+    pub fn move_to_string(&self, m: Move) -> String {
+        let mut ans = self.square_to_algebraic(m.from) + self.square_to_algebraic(m.to).as_str();
+        if let Some(p) = m.promotion {
+            ans.push(Piece::from_type(p, Color::Dark).get_code());
         }
+        ans
+    }
 
-        Board {
-            pieces: board,
-            size: s,
+    // finds the legal move written like move_to_string writes it
+    pub fn move_from_string(&self, s: &str) -> Option<Move> {
+        self.legal_moves().into_iter().find(|m| self.move_to_string(*m) == s)
+    }
+
+    pub fn square_to_algebraic(&self, pos: usize) -> String {
+        let col = pos as i32 % self.size;
+        let rank = self.size - pos as i32 / self.size;
+        format!("{}{}", (b'a' + col as u8) as char, rank)
+    }
+
+    // turns a square like e4 into its index, ranks count up from the bottom of the board
+    pub fn square_from_algebraic(&self, square: &str) -> Option<usize> {
+        let mut chars = square.chars();
+        let file = chars.next()?;
+        if !file.is_ascii_lowercase() {
+            return None;
+        }
+        let col = file as i32 - 'a' as i32;
+        // only plain digits, parse on its own would also take e+3 or e03 as e3
+        let digits = chars.as_str();
+        if digits.starts_with('0') || !digits.chars().all(|c| c.is_ascii_digit()) {
+            return None;
         }
+        let rank = digits.parse::<i32>().ok()?;
+        if col >= self.size || rank < 1 || rank > self.size {
+            return None;
+        }
+        Some(((self.size - rank) * self.size + col) as usize)
     }
 
-    pub fn move_piece(&mut self, o_pos: usize, n_pos: usize, possible_moves:&HashSet<i32>, turn: &mut Color) -> bool {
-        if o_pos == n_pos
-            || o_pos >= self.pieces.len()
-            || n_pos >= self.pieces.len()
-            || self.pieces[o_pos].is_none()
-        {
-            return false;
+    // the one way to change the position, m should be one of the moves from legal_moves
+    pub fn make_move(&mut self, m: Move) {
+        let record = self.apply_move(m);
+        self.history.push(record);
+        self.redo.clear();
+    }
+
+    // takes back the last move, gives back false if there is nothing to undo
+    pub fn unmake_move(&mut self) -> bool {
+        let record = match self.history.pop() {
+            Some(r) => r,
+            None => return false,
+        };
+
+        self.undo(&record);
+        self.redo.push(record.mv);
+        true
+    }
+
+    // plays the last move that was taken back again
+    pub fn redo_move(&mut self) -> bool {
+        let m = match self.redo.pop() {
+            Some(m) => m,
+            None => return false,
+        };
+
+        let record = self.apply_move(m);
+        self.history.push(record);
+        true
+    }
+
+    pub fn last_move(&self) -> Option<Move> {
+        self.history.last().map(|r| r.mv)
+    }
+
+    // every move played on this board in order, not counting ones that have been taken back
+    pub fn get_move_history(&self) -> Vec<Move> {
+        self.history.iter().map(|r| r.mv).collect()
+    }
+
+    // checks if moving the piece on o_pos to n_pos is a pawn reaching the far side of the board
+    pub fn is_promotion(&self, o_pos: usize, n_pos: usize) -> bool {
+        match self.pieces.get(o_pos) {
+            Some(Some(p)) => {
+                p.get_piecetype() == PieceType::Pawn
+                    && n_pos as i32 / self.size == self.home_row(p.get_color().opposite())
+            }
+            _ => false,
         }
+    }
 
-        // if self.pieces[o_pos].unwrap().get_color() != turn {
-        //     return false;
-        // }
+    // finds the legal move between two squares, promotions only match when the piece is given
+    pub fn find_move(&self, from: usize, to: usize, promotion: Option<PieceType>) -> Option<Move> {
+        self.legal_moves()
+            .into_iter()
+            .find(|m| m.from == from && m.to == to && m.promotion == promotion)
+    }
 
-        if !possible_moves.contains(&(n_pos as i32)) {
-            return false;
+    // plays m without checking if it is legal and gives back what is needed to take it back
+    fn apply_move(&mut self, m: Move) -> MoveRecord {
+        let (o_pos, n_pos) = (m.from, m.to);
+        let moving = self.pieces[o_pos].expect("apply_move needs a piece on the starting square");
+        let color = moving.get_color();
+        let mut record = MoveRecord {
+            mv: m,
+            moved: moving,
+            captured: self.pieces[n_pos],
+            captured_pos: n_pos,
+            castling: self.castling,
+            en_passant: self.en_passant,
+            halfmove_clock: self.halfmove_clock,
+            fullmove_number: self.fullmove_number,
+            hash: self.hash,
+        };
+        self.hash ^= zobrist::castling_key(self.castling) ^ self.en_passant_key();
+
+        // when castling the rook jumps over to the square the king crossed
+        if m.has(Move::CASTLE) {
+            let rook = self.rook_home(color, n_pos > o_pos);
+            let crossed = (o_pos + n_pos) / 2;
+            self.set_square(crossed, self.pieces[rook]);
+            self.set_square(rook, None);
+        }
+
+        // en passant takes the pawn that sits beside the moving pawn instead of the one on the target square
+        if m.has(Move::EN_PASSANT) {
+            let captured = o_pos - o_pos % self.size as usize + n_pos % self.size as usize;
+            record.captured = self.pieces[captured];
+            record.captured_pos = captured;
+            self.set_square(captured, None);
+        }
+
+        // only a pawn that just moved two squares can be taken en passant on the next move
+        self.en_passant = None;
+        if m.has(Move::DOUBLE_PUSH) {
+            self.en_passant = Some((o_pos + n_pos) / 2);
+        }
+
+        // castling rights are gone once the king or a rook leaves home or a rook gets captured there
+        for c in [Color::Light, Color::Dark] {
+            for king_side in [true, false] {
+                let rook = self.rook_home(c, king_side);
+                if o_pos == self.king_home(c) || o_pos == rook || n_pos == rook {
+                    self.castling.remove(c, king_side);
+                }
+            }
         }
 
-        // switches player turn if piece successfully moves
-        if *turn == Color::Light {
-            *turn = Color::Dark;
+        let promoted = match m.promotion {
+            Some(piece) => Piece::from_type(piece, color),
+            None => moving,
+        };
+        self.set_square(n_pos, Some(promoted));
+        self.set_square(o_pos, None);
+
+        if moving.get_piecetype() == PieceType::Pawn || record.captured.is_some() {
+            self.halfmove_clock = 0;
         } else {
-            *turn = Color::Light;
+            self.halfmove_clock += 1;
+        }
+        if self.turn == Color::Dark {
+            self.fullmove_number += 1;
         }
 
-        self.pieces[n_pos] = self.pieces[o_pos];
-        self.pieces[o_pos] = None;
-        true
+        // switches player turn once the piece has moved
+        self.turn = self.turn.opposite();
+        self.hash ^= zobrist::castling_key(self.castling) ^ self.en_passant_key() ^ zobrist::side_key();
+        record
+    }
+
+    // the en passant file only counts when the side to move has a pawn beside the one that moved, otherwise the
+    // position is the same as one without the en passant square and has to hash the same for repetitions
+    fn en_passant_key(&self) -> u64 {
+        let sq = match self.en_passant {
+            Some(sq) => sq as i32,
+            None => return 0,
+        };
+        let forward = match self.turn {
+            Color::Light => 1,
+            Color::Dark => -1,
+        };
+        let pawn = Some(Piece::from_type(PieceType::Pawn, self.turn));
+        let can_capture = [-1, 1].iter().any(|&dc| match self.offset(sq, forward, dc) {
+            Some(v) => self.pieces[v as usize] == pawn,
+            None => false,
+        });
+        match can_capture {
+            true => zobrist::en_passant_key(sq as usize % self.size as usize),
+            false => 0,
+        }
+    }
+
+    // hashes the position from nothing, the incremental updates in make_move and unmake_move must always agree with this
+    fn compute_hash(&self) -> u64 {
+        let mut hash = zobrist::castling_key(self.castling) ^ self.en_passant_key();
+        if self.turn == Color::Dark {
+            hash ^= zobrist::side_key();
+        }
+        for (pos, p) in self.pieces.iter().enumerate() {
+            if let Some(p) = p {
+                hash ^= zobrist::piece_key(pos, *p);
+            }
+        }
+        hash
+    }
+
+    // identifies the position for repetition checks and transposition tables
+    pub fn hash(&self) -> u64 {
+        self.hash
+    }
+
+    // every change to the pieces goes through here so the bitboards never fall out of sync with the mailbox
+    fn set_square(&mut self, pos: usize, piece: Option<Piece>) {
+        if let Some(old) = self.pieces[pos] {
+            self.hash ^= zobrist::piece_key(pos, old);
+        }
+        if let Some(new) = piece {
+            self.hash ^= zobrist::piece_key(pos, new);
+        }
+        if let Some(bb) = &mut self.bitboards {
+            if let Some(old) = self.pieces[pos] {
+                bb.toggle(pos, old);
+            }
+            if let Some(new) = piece {
+                bb.toggle(pos, new);
+            }
+        }
+        self.pieces[pos] = piece;
+    }
+
+    // puts the board back to how it was before the recorded move
+    fn undo(&mut self, record: &MoveRecord) {
+        let m = record.mv;
+        self.set_square(m.to, None);
+        self.set_square(m.from, Some(record.moved));
+        self.set_square(record.captured_pos, record.captured);
+
+        // puts the rook back in the corner if the king castled
+        if m.has(Move::CASTLE) {
+            let rook = self.rook_home(record.moved.get_color(), m.to > m.from);
+            let crossed = (m.from + m.to) / 2;
+            self.set_square(rook, self.pieces[crossed]);
+            self.set_square(crossed, None);
+        }
+
+        self.castling = record.castling;
+        self.en_passant = record.en_passant;
+        self.halfmove_clock = record.halfmove_clock;
+        self.fullmove_number = record.fullmove_number;
+        self.turn = record.moved.get_color();
+        self.hash = record.hash;
+    }
+
+    fn home_row(&self, color: Color) -> i32 {
+        match color {
+            Color::Light => self.size - 1,
+            Color::Dark => 0,
+        }
+    }
+
+    // kings start in the middle of the back rank (the e file on a typical 8*8 board)
+    fn king_home(&self, color: Color) -> usize {
+        (self.home_row(color) * self.size + self.size / 2) as usize
+    }
+
+    fn rook_home(&self, color: Color, king_side: bool) -> usize {
+        let col = match king_side {
+            true => self.size - 1,
+            false => 0,
+        };
+        (self.home_row(color) * self.size + col) as usize
+    }
+
+    // the king needs two squares towards the rook and must not land on or past it
+    fn can_ever_castle(&self, color: Color, king_side: bool) -> bool {
+        let king_col = self.size / 2;
+        let room = match king_side {
+            true => king_col + 2 < self.size - 1,
+            false => king_col - 2 > 0,
+        };
+        let is = |pos: usize, piece: PieceType| match self.pieces[pos] {
+            Some(p) => p.get_color() == color && p.get_piecetype() == piece,
+            None => false,
+        };
+        room && is(self.king_home(color), PieceType::King) && is(self.rook_home(color, king_side), PieceType::Rook)
+    }
+
+    // gives the square that is dr rows and dc columns away from pos or None if that falls off the board
+    fn offset(&self, pos: i32, dr: i32, dc: i32) -> Option<i32> {
+        let row = pos / self.size + dr;
+        let col = pos % self.size + dc;
+        if row < 0 || row >= self.size || col < 0 || col >= self.size {
+            return None;
+        }
+        Some(row * self.size + col)
+    }
+
+    fn find_king(&self, color: Color) -> Option<usize> {
+        if let Some(bb) = &self.bitboards {
+            let king = bb.pieces(PieceType::King, color);
+            return match king {
+                0 => None,
+                _ => Some(king.trailing_zeros() as usize),
+            };
+        }
+
+        self.pieces.iter().position(|p| {
+            matches!(p, Some(piece) if piece.get_piecetype() == PieceType::King && piece.get_color() == color)
+        })
+    }
+
+    // checks if any piece of color by could capture on pos
+    pub fn is_square_attacked(&self, pos: usize, by: Color) -> bool {
+        if let Some(bb) = &self.bitboards {
+            return bb.is_attacked(pos, by);
+        }
+
+        let pos = pos as i32;
+        let attacker_at = |sq: Option<i32>, types: &[PieceType]| -> bool {
+            match sq.and_then(|v| self.pieces[v as usize]) {
+                Some(p) => p.get_color() == by && types.contains(&p.get_piecetype()),
+                None => false,
+            }
+        };
+
+        // light pawns attack upwards so they sit one row below the square they attack
+        let pawn_row = match by {
+            Color::Light => 1,
+            Color::Dark => -1,
+        };
+        if attacker_at(self.offset(pos, pawn_row, -1), &[PieceType::Pawn])
+            || attacker_at(self.offset(pos, pawn_row, 1), &[PieceType::Pawn])
+        {
+            return true;
+        }
+
+        let knight = [(-2, -1), (-2, 1), (-1, -2), (-1, 2), (1, -2), (1, 2), (2, -1), (2, 1)];
+        if knight.iter().any(|&(dr, dc)| attacker_at(self.offset(pos, dr, dc), &[PieceType::Knight])) {
+            return true;
+        }
+
+        let king = [(-1, -1), (-1, 0), (-1, 1), (0, -1), (0, 1), (1, -1), (1, 0), (1, 1)];
+        if king.iter().any(|&(dr, dc)| attacker_at(self.offset(pos, dr, dc), &[PieceType::King])) {
+            return true;
+        }
+
+        // walks each ray until the first piece and checks if it is a slider that moves along that ray
+        let rays = [
+            ((-1, 0), PieceType::Rook),
+            ((1, 0), PieceType::Rook),
+            ((0, -1), PieceType::Rook),
+            ((0, 1), PieceType::Rook),
+            ((-1, -1), PieceType::Bishop),
+            ((-1, 1), PieceType::Bishop),
+            ((1, -1), PieceType::Bishop),
+            ((1, 1), PieceType::Bishop),
+        ];
+        for ((dr, dc), slider) in rays.iter() {
+            let mut sq = self.offset(pos, *dr, *dc);
+            while let Some(v) = sq {
+                if self.pieces[v as usize].is_some() {
+                    if attacker_at(sq, &[*slider, PieceType::Queen]) {
+                        return true;
+                    }
+                    break;
+                }
+                sq = self.offset(v, *dr, *dc);
+            }
+        }
+
+        false
+    }
+
+    // a side without a king on the board (like the n queens boards) is never in check
+    pub fn in_check(&self, color: Color) -> bool {
+        match self.find_king(color) {
+            Some(king) => self.is_square_attacked(king, color.opposite()),
+            None => false,
+        }
+    }
+
+    // the cheapest piece of color by that attacks pos when the board looks like squares, used by see where pieces
+    // get taken off one at a time so sliders behind them start to count
+    fn least_valuable_attacker(&self, squares: &[Option<Piece>], pos: usize, by: Color) -> Option<usize> {
+        let pos = pos as i32;
+        let mut best: Option<(i32, usize)> = None;
+        let mut consider = |sq: i32, types: &[PieceType]| {
+            if let Some(p) = squares[sq as usize] {
+                let value = piece_value(p.get_piecetype());
+                if p.get_color() == by && types.contains(&p.get_piecetype()) && best.is_none_or(|(v, _)| value < v) {
+                    best = Some((value, sq as usize));
+                }
+            }
+        };
+
+        let pawn_row = match by {
+            Color::Light => 1,
+            Color::Dark => -1,
+        };
+        let pawn = [(pawn_row, -1), (pawn_row, 1)];
+        let knight = [(-2, -1), (-2, 1), (-1, -2), (-1, 2), (1, -2), (1, 2), (2, -1), (2, 1)];
+        let king = [(-1, -1), (-1, 0), (-1, 1), (0, -1), (0, 1), (1, -1), (1, 0), (1, 1)];
+        for (steps, piece) in [(&pawn[..], PieceType::Pawn), (&knight[..], PieceType::Knight), (&king[..], PieceType::King)] {
+            for &(dr, dc) in steps {
+                if let Some(sq) = self.offset(pos, dr, dc) {
+                    consider(sq, &[piece]);
+                }
+            }
+        }
+
+        for (dr, dc) in king {
+            let slider = match dr == 0 || dc == 0 {
+                true => PieceType::Rook,
+                false => PieceType::Bishop,
+            };
+            let mut sq = self.offset(pos, dr, dc);
+            while let Some(v) = sq {
+                if squares[v as usize].is_some() {
+                    consider(v, &[slider, PieceType::Queen]);
+                    break;
+                }
+                sq = self.offset(v, dr, dc);
+            }
+        }
+        best.map(|(_, sq)| sq)
+    }
+
+    // static exchange evaluation, what the mover comes out with in centipawns if both sides keep recapturing on the
+    // target square with their cheapest piece and either side can stop when carrying on would lose more
+    pub fn see(&self, m: Move) -> i32 {
+        let mut squares = self.pieces.clone();
+        let mover = match squares[m.from] {
+            Some(p) => p,
+            None => return 0,
+        };
+
+        let mut captured = match squares[m.to] {
+            Some(p) => piece_value(p.get_piecetype()),
+            None => 0,
+        };
+        if m.has(Move::EN_PASSANT) {
+            let behind = m.to as i32 + (m.from as i32 / self.size - m.to as i32 / self.size) * self.size;
+            squares[behind as usize] = None;
+            captured = piece_value(PieceType::Pawn);
+        }
+        let mut on_square = piece_value(mover.get_piecetype());
+        if let Some(piece) = m.promotion {
+            captured += piece_value(piece) - on_square;
+            on_square = piece_value(piece);
+        }
+        squares[m.from] = None;
+
+        // gain[d] is what the side making capture d has won if the exchange stops right after it
+        let mut gain = vec![captured];
+        let mut side = mover.get_color().opposite();
+        while let Some(from) = self.least_valuable_attacker(&squares, m.to, side) {
+            gain.push(on_square - gain[gain.len() - 1]);
+            on_square = piece_value(squares[from].unwrap().get_piecetype());
+            squares[from] = None;
+            side = side.opposite();
+        }
+
+        // each side only makes its capture when it is better than stopping
+        while gain.len() > 1 {
+            let last = gain.pop().unwrap();
+            let prev = gain.len() - 1;
+            gain[prev] = -(-gain[prev]).max(last);
+        }
+        gain[0]
+    }
+
+    // plays the move and makes sure it does not leave the mover's king attacked before taking it back
+    fn is_legal(&mut self, m: Move) -> bool {
+        let color = self.turn;
+        let record = self.apply_move(m);
+        let legal = !self.in_check(color);
+        self.undo(&record);
+        legal
+    }
+
+    // a copy of the board without the moves that led to it, which is all is_legal needs and is much cheaper to make
+    // than a full clone once the game gets long
+    fn position_only(&self) -> Board {
+        Board {
+            pieces: self.pieces.clone(),
+            size: self.size,
+            castling: self.castling,
+            en_passant: self.en_passant,
+            turn: self.turn,
+            halfmove_clock: self.halfmove_clock,
+            fullmove_number: self.fullmove_number,
+            history: vec![],
+            redo: vec![],
+            bitboards: self.bitboards,
+            hash: self.hash,
+        }
     }
 
     // used for the below piece functions
@@ -241,25 +1110,40 @@ impl Board {
     fn pawn_moves(&self, pos:usize, color:Color) -> HashSet<i32> {
         let pos = pos as i32;
         let mut ans = HashSet::new();
-        match color {
-            Color::Light => {
-                if self.pieces[(pos-self.size) as usize].is_none() {
-                    ans.insert(pos-self.size);
-                    // checks second rank
-                    if pos/self.size == self.size - 2 && self.pieces[(pos-self.size*2) as usize].is_none() {
-                        ans.insert(pos-self.size*2);
+
+        // light pawns walk up the board and dark pawns walk down it
+        let (forward, start_row) = match color {
+            Color::Light => (-1, self.size - 2),
+            Color::Dark => (1, 1),
+        };
+
+        // offset keeps every square on the board so a pawn on the last rank simply has no pushes
+        if let Some(one) = self.offset(pos, forward, 0) {
+            if self.pieces[one as usize].is_none() {
+                ans.insert(one);
+                // checks second rank (7th rank for dark on a typical 8*8 board)
+                if pos / self.size == start_row {
+                    if let Some(two) = self.offset(pos, forward * 2, 0) {
+                        if self.pieces[two as usize].is_none() {
+                            ans.insert(two);
+                        }
                     }
                 }
-            },
-            Color::Dark => {
-                if self.pieces[(pos+self.size) as usize].is_none() {
-                    ans.insert(pos+self.size);
-                    // checks 7th rank for a typical 8*8 board
-                    if pos/self.size == 1 && self.pieces[(pos+self.size*2) as usize].is_none() {
-                        ans.insert(pos+self.size*2);
-                    }
+            }
+        }
+
+        // diagonal captures, offset stops pawns on the edge files from wrapping around to the other side
+        for side in [-1, 1] {
+            if let Some(v) = self.offset(pos, forward, side) {
+                let enemy = match self.pieces[v as usize] {
+                    Some(p) => p.get_color() != color,
+                    None => false,
+                };
+                // en passant is the only capture onto an empty square
+                if enemy || Some(v as usize) == self.en_passant {
+                    ans.insert(v);
                 }
-            },
+            }
         }
         ans
     }
@@ -267,11 +1151,11 @@ impl Board {
     fn knight_moves(&self, pos:usize, color:Color) -> HashSet<i32> {
         let pos = pos as i32;
         let mut ans = HashSet::new();
-        let moves = vec![pos - self.size*2 - 1, pos - self.size*2 + 1, pos - 2 - self.size, pos + 2 - self.size, pos + self.size - 2, pos + self.size + 2, pos + self.size * 2 - 1, pos + self.size * 2 + 1];
+        let moves = [(-2, -1), (-2, 1), (-1, -2), (-1, 2), (1, -2), (1, 2), (2, -1), (2, 1)];
 
-        for mov in moves.iter() {
-            if mov >= &0 && mov < &(self.size*self.size) {
-                if let Some(v) = self.is_safe(*mov, color) {
+        for (dr, dc) in moves.iter() {
+            if let Some(mov) = self.offset(pos, *dr, *dc) {
+                if let Some(v) = self.is_safe(mov, color) {
                     ans.insert(v);
                 }
             }
@@ -404,51 +1288,455 @@ impl Board {
     fn king_moves(&self, pos:usize, color:Color) -> HashSet<i32> {
         let pos = pos as i32;
         let mut ans = HashSet::new();
-        let moves = vec![pos-self.size-1, pos-self.size, pos-self.size+1, pos-1, pos+1, pos+self.size-1, pos+self.size, pos+self.size+1];
+        let moves = [(-1, -1), (-1, 0), (-1, 1), (0, -1), (0, 1), (1, -1), (1, 0), (1, 1)];
 
-        for mov in moves.iter() {
-            if mov >= &0 && mov < &(self.size*self.size) {
-                if let Some(v) = self.is_safe(*mov, color) {
+        for (dr, dc) in moves.iter() {
+            if let Some(mov) = self.offset(pos, *dr, *dc) {
+                if let Some(v) = self.is_safe(mov, color) {
                     ans.insert(v);
                 }
             }
         }
 
+        ans.extend(self.castling_moves(pos as usize, color));
+        ans
+    }
+
+    // castling is given as the king moving two squares towards the rook
+    fn castling_moves(&self, pos: usize, color: Color) -> HashSet<i32> {
+        let mut ans = HashSet::new();
+        if pos != self.king_home(color) || self.is_square_attacked(pos, color.opposite()) {
+            return ans;
+        }
+
+        for king_side in [true, false] {
+            if !self.castling.get(color, king_side) || !self.can_ever_castle(color, king_side) {
+                continue;
+            }
+
+            let rook = self.rook_home(color, king_side);
+            let (lo, hi) = match king_side {
+                true => (pos + 1, rook),
+                false => (rook + 1, pos),
+            };
+            if (lo..hi).any(|sq| self.pieces[sq].is_some()) {
+                continue;
+            }
+
+            // the king may not pass through or land on an attacked square
+            let (crossed, target) = match king_side {
+                true => (pos + 1, pos + 2),
+                false => (pos - 1, pos - 2),
+            };
+            if !self.is_square_attacked(crossed, color.opposite())
+                && !self.is_square_attacked(target, color.opposite())
+            {
+                ans.insert(target as i32);
+            }
+        }
+        ans
+    }
+
+    // every move for the side to move that does not leave its own king in check
+    pub fn legal_moves(&self) -> Vec<Move> {
+        let mut scratch = self.position_only();
+        let mut ans = vec![];
+        for pos in 0..self.pieces.len() {
+            for m in self.pseudo_moves(pos) {
+                if scratch.is_legal(m) {
+                    ans.push(m);
+                }
+            }
+        }
         ans
     }
 
-    pub fn get_valid_moves(&self, pos: usize) -> HashSet<i32> {
-        let p = self.pieces.get(pos);
-        if p.is_none() {
-            return HashSet::new();
+    // the legal moves of the piece on pos, empty if it is not that piece's turn
+    pub fn get_valid_moves(&self, pos: usize) -> Vec<Move> {
+        self.legal_moves().into_iter().filter(|m| m.from == pos).collect()
+    }
+
+    // the squares the piece on pos can reach ignoring king safety, castling moves are included for a king
+    fn piece_targets(&self, pos: usize, p: Piece) -> Vec<usize> {
+        match &self.bitboards {
+            Some(bb) => {
+                let mut squares = bb.targets(pos, p, self.en_passant);
+                let mut targets = vec![];
+                while squares != 0 {
+                    targets.push(pop_lsb(&mut squares));
+                }
+                if p.get_piecetype() == PieceType::King {
+                    targets.extend(self.castling_moves(pos, p.get_color()).into_iter().map(|v| v as usize));
+                }
+                targets
+            }
+            None => {
+                let targets = match p.get_piecetype() {
+                    PieceType::Pawn => {
+                        Self::pawn_moves(self, pos, p.get_color())
+                    }
+                    PieceType::Knight => {
+                        Self::knight_moves(self, pos, p.get_color())
+                    }
+                    PieceType::Bishop => Self::bishop_moves(self, pos, p.get_color()),
+                    PieceType::Rook => Self::rook_moves(self, pos, p.get_color()),
+                    PieceType::Queen => Self::queen_moves(self, pos, p.get_color()),
+                    PieceType::King => {
+                        Self::king_moves(self, pos, p.get_color())
+                    }
+                };
+                targets.into_iter().map(|v| v as usize).collect()
+            }
         }
-        let p = p.unwrap();
-        if p.is_none() {
-            return HashSet::new();
+    }
+
+    // how many squares the piece on pos could move to whichever side is to move, used by the evaluation
+    pub fn mobility(&self, pos: usize) -> usize {
+        let p = match self.pieces.get(pos) {
+            Some(Some(p)) => *p,
+            _ => return 0,
+        };
+        match &self.bitboards {
+            Some(bb) => bb.targets(pos, p, None).count_ones() as usize,
+            None => self.piece_targets(pos, p).len(),
         }
+    }
+
+    // every move the piece on pos could make while ignoring king safety, only for the side to move
+    fn pseudo_moves(&self, pos: usize) -> Vec<Move> {
+        let p = match self.pieces.get(pos) {
+            Some(Some(p)) if p.get_color() == self.turn => *p,
+            _ => return vec![],
+        };
+
+        // sorted so the same position always gives its moves in the same order
+        let mut targets = self.piece_targets(pos, p);
+        targets.sort_unstable();
 
-        let p = p.unwrap();
-        match p.get_piecetype() {
-            PieceType::Pawn => {
-                Self::pawn_moves(self, pos, p.get_color())
+        let mut ans = vec![];
+        for to in targets {
+            let mut flags = Move::QUIET;
+            if self.pieces[to].is_some() {
+                flags |= Move::CAPTURE;
             }
-            PieceType::Knight => {
-                Self::knight_moves(self, pos, p.get_color())
+
+            match p.get_piecetype() {
+                PieceType::Pawn => {
+                    if Some(to) == self.en_passant && to % self.size as usize != pos % self.size as usize {
+                        flags |= Move::CAPTURE | Move::EN_PASSANT;
+                    }
+                    if (to as i32 - pos as i32).abs() == self.size * 2 {
+                        flags |= Move::DOUBLE_PUSH;
+                    }
+                    if self.is_promotion(pos, to) {
+                        for piece in PROMOTION_PIECES {
+                            ans.push(Move { from: pos, to, flags: flags | Move::PROMOTION, promotion: Some(piece) });
+                        }
+                        continue;
+                    }
+                }
+                // the king only ever moves two files when castling
+                PieceType::King if (to as i32 - pos as i32).abs() == 2 && to / self.size as usize == pos / self.size as usize => {
+                    flags |= Move::CASTLE
+                }
+                _ => (),
             }
-            PieceType::Bishop => Self::bishop_moves(self, pos, p.get_color()),
-            PieceType::Rook => Self::rook_moves(self, pos, p.get_color()),
-            PieceType::Queen => Self::queen_moves(self, pos, p.get_color()),
-            PieceType::King => {
-                Self::king_moves(self, pos, p.get_color())
+            ans.push(Move { from: pos, to, flags, promotion: None });
+        }
+        ans
+    }
+
+    // the game is over once the side to move has no legal moves left
+    // the game is also over when one of the automatic draw rules kicks in, though a checkmate delivered on the move
+    // that reaches the seventy five move limit still counts
+    pub fn game_status(&self) -> GameStatus {
+        if self.legal_moves().is_empty() {
+            return match self.in_check(self.turn) {
+                true => GameStatus::Checkmate(self.turn.opposite()),
+                false => GameStatus::Stalemate,
+            };
+        }
+
+        if self.repetition_count() >= 5 {
+            return GameStatus::Draw(DrawReason::FivefoldRepetition);
+        }
+        if self.halfmove_clock >= 150 {
+            return GameStatus::Draw(DrawReason::SeventyFiveMoveRule);
+        }
+        if self.is_insufficient_material() {
+            return GameStatus::Draw(DrawReason::InsufficientMaterial);
+        }
+        GameStatus::Ongoing
+    }
+
+    // a draw the side to move could claim right now if it wanted to
+    pub fn claimable_draw(&self) -> Option<DrawReason> {
+        if self.repetition_count() >= 3 {
+            return Some(DrawReason::ThreefoldRepetition);
+        }
+        if self.halfmove_clock >= 100 {
+            return Some(DrawReason::FiftyMoveRule);
+        }
+        None
+    }
+
+    // how many times the current position has come up, counting this time
+    pub fn repetition_count(&self) -> usize {
+        // a capture or pawn move can never be undone so nothing before the last one can repeat,
+        // the hash already includes the side to move, castling rights and en passant file
+        let reversible = (self.halfmove_clock as usize).min(self.history.len());
+        let earlier = self.history[self.history.len() - reversible..]
+            .iter()
+            .filter(|r| r.hash == self.hash)
+            .count();
+        earlier + 1
+    }
+
+    // dead positions where no sequence of moves can end in checkmate: king against king with at most one minor piece,
+    // or kings and bishops where every bishop stands on the same colored squares
+    pub fn is_insufficient_material(&self) -> bool {
+        let mut minors = 0;
+        let mut bishop_squares = [false; 2];
+        let mut knights = false;
+        for (pos, p) in self.pieces.iter().enumerate() {
+            let p = match p {
+                Some(p) => p,
+                None => continue,
+            };
+            match p.get_piecetype() {
+                PieceType::King => (),
+                PieceType::Knight => {
+                    minors += 1;
+                    knights = true;
+                }
+                PieceType::Bishop => {
+                    minors += 1;
+                    let shade = (pos as i32 / self.size + pos as i32 % self.size) % 2;
+                    bishop_squares[shade as usize] = true;
+                }
+                _ => return false,
             }
         }
+        let mixed_bishops = bishop_squares[0] && bishop_squares[1];
+        minors <= 1 || !(knights || mixed_bishops)
+    }
+
+    // the same position with the bitboards dropped so tests can check the mailbox generator on an 8*8 board
+    #[cfg(test)]
+    pub fn mailbox_only(&self) -> Board {
+        let mut b = self.clone();
+        b.bitboards = None;
+        b
+    }
+
+    pub fn get_turn(&self) -> Color {
+        self.turn
+    }
+
+    pub fn get_fullmove_number(&self) -> u32 {
+        self.fullmove_number
     }
 
     pub fn get_size(&self) -> i32 {
         self.size
     }
 
+    // the piece on pos without copying the whole board like get_pieces does
+    pub fn piece_at(&self, pos: usize) -> Option<Piece> {
+        self.pieces.get(pos).copied().flatten()
+    }
+
     pub fn get_pieces(&self) -> Vec<Option<Piece>> {
         self.pieces.clone()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn play(b: &mut Board, moves: &[&str]) {
+        for mv in moves {
+            let m = b.legal_moves().into_iter().find(|m| b.move_to_string(*m) == *mv).unwrap();
+            b.make_move(m);
+        }
+    }
+
+    fn see(fen: &str, mv: &str) -> i32 {
+        let b = Board::from_fen(fen, 8).unwrap();
+        let m = b.legal_moves().into_iter().find(|m| b.move_to_string(*m) == mv).unwrap();
+        b.see(m)
+    }
+
+    #[test]
+    fn static_exchange_evaluation() {
+        let pawn = piece_value(PieceType::Pawn);
+        let knight = piece_value(PieceType::Knight);
+        // the pawn on e5 is defended by nothing that can get to it
+        assert_eq!(see("1k1r4/1pp4p/p7/4p3/8/P5P1/1PP4P/2K1R3 w - - 0 1", "e1e5"), pawn);
+        // the knight takes a pawn defended twice and gets taken back, the queen behind the rook doesn't help
+        assert_eq!(see("1k1r3q/1ppn3p/p4b2/4p3/8/P2N2P1/1PP1R1BP/2K1Q3 w - - 0 1", "d3e5"), pawn - knight);
+        // a quiet move onto a square a pawn attacks loses the piece
+        assert_eq!(see("4k3/8/8/2p5/8/8/2N5/4K3 w - - 0 1", "c2d4"), -knight);
+        assert_eq!(see("4k3/8/8/2p5/8/8/2N5/4K3 w - - 0 1", "c2e3"), 0);
+    }
+
+    #[test]
+    fn redo_plays_back_what_was_undone() {
+        let mut b = Board::from_fen(START_FEN, 8).unwrap();
+        play(&mut b, &["e2e4", "c7c5"]);
+        let (fen, hash) = (b.to_fen(), b.hash());
+
+        assert!(b.unmake_move());
+        assert!(b.unmake_move());
+        assert_eq!(b.to_fen(), START_FEN);
+        assert!(b.redo_move());
+        assert!(b.redo_move());
+        assert!(!b.redo_move());
+        assert_eq!((b.to_fen(), b.hash()), (fen, hash));
+
+        // a different move after an undo throws away what could have been redone
+        b.unmake_move();
+        play(&mut b, &["e7e5"]);
+        assert!(!b.redo_move());
+        assert_eq!(b.get_move_history().len(), 2);
+    }
+
+    #[test]
+    fn knight_shuffles_repeat_the_position() {
+        let mut b = Board::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", 8).unwrap();
+        let shuffle = ["g1f3", "g8f6", "f3g1", "f6g8"];
+        play(&mut b, &shuffle);
+        assert_eq!(b.repetition_count(), 2);
+        assert_eq!(b.claimable_draw(), None);
+
+        play(&mut b, &shuffle);
+        assert_eq!(b.claimable_draw(), Some(DrawReason::ThreefoldRepetition));
+        assert_eq!(b.game_status(), GameStatus::Ongoing);
+
+        play(&mut b, &shuffle);
+        play(&mut b, &shuffle);
+        assert_eq!(b.game_status(), GameStatus::Draw(DrawReason::FivefoldRepetition));
+
+        b.unmake_move();
+        assert_eq!(b.game_status(), GameStatus::Ongoing);
+
+        // the en passant square after 1.e4 can't be used so coming back to that position later still repeats it
+        let mut b = Board::from_fen(START_FEN, 8).unwrap();
+        play(&mut b, &["e2e4", "g8f6", "g1f3", "f6g8", "f3g1", "g8f6", "g1f3", "f6g8", "f3g1"]);
+        assert_eq!(b.repetition_count(), 3);
+        assert_eq!(b.claimable_draw(), Some(DrawReason::ThreefoldRepetition));
+
+        // while one that could be taken makes the position different
+        let hash = |fen: &str| Board::from_fen(fen, 8).unwrap().hash();
+        assert_ne!(hash("4k3/8/8/8/4Pp2/8/8/4K3 b - e3 0 1"), hash("4k3/8/8/8/4Pp2/8/8/4K3 b - - 0 1"));
+        assert_eq!(hash("4k3/8/8/8/4P1p1/8/8/4K3 b - e3 0 1"), hash("4k3/8/8/8/4P1p1/8/8/4K3 b - - 0 1"));
+    }
+
+    #[test]
+    fn fen_round_trips() {
+        for (fen, size) in [
+            (START_FEN, 8),
+            ("r3k2r/8/8/8/4pP2/8/8/R3K2R b KQkq f3 0 1", 8),
+            ("rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3", 8),
+            ("k9/10/10/10/10/10/10/10/10/9K w - - 12 30", 10),
+            ("r4k4r/11/11/11/3p7/11/11/11/11/11/R4K4R b KQkq - 0 1", 11),
+        ] {
+            let b = Board::from_fen(fen, size).unwrap();
+            assert_eq!(b.to_fen(), fen);
+            assert_eq!(Board::from_fen(&b.to_fen(), size).unwrap().to_fen(), fen);
+        }
+    }
+
+    #[test]
+    fn fen_errors_point_at_the_problem() {
+        for (fen, error) in [
+            ("rnbqkbnr/ppppxppp/8/8/8/8/PPPPPPPP/RNBQKBNR", FenError::BadPiece { column: 13, found: 'x' }),
+            ("rnbqkbnr/ppppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR", FenError::RankTooLong { column: 17, rank: 1 }),
+            ("rnbqkbnr/pppppppp/9/8/8/8/PPPPPPPP/RNBQKBNR", FenError::RankTooLong { column: 18, rank: 2 }),
+            ("rnbqkbnr/ppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR", FenError::RankTooShort { column: 16, rank: 1 }),
+            ("4k3/8/8/8/8/8/4K3", FenError::WrongRankCount { column: 17, expected: 8 }),
+            ("4k3/8/8/8/8/8/8/8/4K3", FenError::WrongRankCount { column: 21, expected: 8 }),
+            ("8/8/8/8/8/8/8/4K3", FenError::MissingKing(Color::Dark)),
+            ("4k3/8/8/8/8/8/8/4K1K1", FenError::TooManyKings(Color::Light)),
+            ("4k2P/8/8/8/8/8/8/4K3", FenError::PawnOnBackRank { column: 3 }),
+            ("4k3/8/8/8/8/8/8/4K3 x", FenError::BadSideToMove { column: 20 }),
+            ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KKQkq", FenError::BadCastling { column: 47, found: 'K' }),
+            ("4k3/8/8/8/8/8/8/4K3 w K", FenError::BadCastling { column: 22, found: 'K' }),
+            ("4k3/8/8/8/8/8/8/4K3 w - e9", FenError::BadEnPassant { column: 24 }),
+            ("4k3/8/8/8/8/8/8/4K3 w - - x 1", FenError::BadClock { column: 26 }),
+            ("4k3/8/8/8/8/8/8/4K3 w - - 0 -3", FenError::BadClock { column: 28 }),
+            ("4k3/8/8/8/8/8/8/4K3 w - - 0 0", FenError::BadClock { column: 28 }),
+            ("4k3/8/8/8/4Pp2/8/8/4K3 b - e+3", FenError::BadEnPassant { column: 27 }),
+            ("4k3/8/8/8/4Pp2/8/8/4K3 b - e03", FenError::BadEnPassant { column: 27 }),
+            ("4k3/8/8/8/8/8/8/4K3 w - - 0 1 z", FenError::TooManyFields { column: 30 }),
+        ] {
+            assert_eq!(Board::from_fen(fen, 8).unwrap_err(), error, "{}", fen);
+        }
+    }
+
+    #[test]
+    fn reads_the_fields_after_the_pieces() {
+        let b = Board::from_fen("r3k2r/8/8/8/4pP2/8/8/R3K2R b Kq f3 3 41", 8).unwrap();
+        assert_eq!(b.turn, Color::Dark);
+        assert_eq!(b.castling, CastlingRights { light_king_side: true, dark_queen_side: true, ..CastlingRights::none() });
+        assert_eq!(b.en_passant, b.square_from_algebraic("f3"));
+        assert_eq!((b.halfmove_clock, b.fullmove_number), (3, 41));
+
+        // missing fields fall back to a fresh game, castling only where the king and rook are at home
+        let b = Board::from_fen("r3k3/8/8/8/8/8/8/4K2R", 8).unwrap();
+        assert_eq!(b.turn, Color::Light);
+        assert_eq!(b.castling, CastlingRights { light_king_side: true, dark_queen_side: true, ..CastlingRights::none() });
+        assert_eq!((b.en_passant, b.halfmove_clock, b.fullmove_number), (None, 0, 1));
+    }
+
+    #[test]
+    fn en_passant_needs_the_pawn_that_just_moved() {
+        assert!(Board::from_fen("4k3/8/8/4pP2/8/8/8/4K3 w - e6 0 1", 8).is_ok());
+        for fen in [
+            // no pawn in front of the square
+            "4k3/8/8/8/8/8/8/4K3 w - e6 0 1",
+            // the pawn is the side to move's own
+            "4k3/8/8/4P3/8/8/8/4K3 w - e6 0 1",
+            // something stands on the square the pawn skipped or started from
+            "4k3/8/4n3/4p3/8/8/8/4K3 w - e6 0 1",
+            "4k3/4n3/8/4p3/8/8/8/4K3 w - e6 0 1",
+            // wrong rank for the side to move
+            "4k3/8/8/8/4P3/8/8/4K3 w - e3 0 1",
+        ] {
+            let column = fen.find(" e").unwrap() + 1;
+            assert_eq!(Board::from_fen(fen, 8).unwrap_err(), FenError::BadEnPassant { column }, "{}", fen);
+        }
+
+        // on a 3*3 board the squares around the en passant square fall off the edge
+        assert_eq!(Board::from_fen("2k/3/1K1 w - a1", 3).unwrap_err(), FenError::BadEnPassant { column: 13 });
+        assert_eq!(Board::diagram_from_fen("3/3/3 b - a3", 3).unwrap_err(), FenError::BadEnPassant { column: 10 });
+    }
+
+    #[test]
+    fn move_clock_draws() {
+        let b = Board::from_fen("4k3/8/8/8/8/8/8/R3K3 w - - 100 80", 8).unwrap();
+        assert_eq!(b.claimable_draw(), Some(DrawReason::FiftyMoveRule));
+        assert_eq!(b.game_status(), GameStatus::Ongoing);
+
+        let b = Board::from_fen("4k3/8/8/8/8/8/8/R3K3 w - - 150 105", 8).unwrap();
+        assert_eq!(b.game_status(), GameStatus::Draw(DrawReason::SeventyFiveMoveRule));
+
+        // mate on the move that hits the limit still wins
+        let b = Board::from_fen("R3k3/8/4K3/8/8/8/8/8 b - - 150 105", 8).unwrap();
+        assert_eq!(b.game_status(), GameStatus::Checkmate(Color::Light));
+    }
+
+    #[test]
+    fn dead_positions() {
+        for fen in ["4k3/8/8/8/8/8/8/4K3", "4k3/8/8/8/8/8/8/2B1K3", "4k3/8/8/8/8/8/8/1N2K3", "2b1k3/8/8/8/8/8/8/3BK3"] {
+            let b = Board::from_fen(fen, 8).unwrap();
+            assert_eq!(b.game_status(), GameStatus::Draw(DrawReason::InsufficientMaterial), "{}", fen);
+        }
+        // bishops on opposite colors, two knights and a lone pawn can all still mate with help
+        for fen in ["3bk3/8/8/8/8/8/8/3BK3", "4k3/8/8/8/8/8/8/1NN1K3", "4k3/8/8/8/8/8/4P3/4K3"] {
+            let b = Board::from_fen(fen, 8).unwrap();
+            assert_eq!(b.game_status(), GameStatus::Ongoing, "{}", fen);
+        }
+    }
+}
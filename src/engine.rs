@@ -0,0 +1,432 @@
+// a negamax alpha-beta search with iterative deepening so there is something to play against
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::bitboard::{color_index, piece_index};
+use crate::board::*;
+use crate::eval::{evaluate, piece_value};
+use crate::tt::{Bound, TranspositionTable};
+
+// scores at or above this are forced mates, the distance to mate is taken off so quicker mates score higher
+pub const MATE: i32 = 1_000_000;
+// scores this close to MATE are mates, no search gets anywhere near this many plies deep
+pub const MATE_BOUND: i32 = MATE - 1000;
+const INFINITY: i32 = MATE + 1;
+
+// how often the clock gets checked, in nodes
+const TIME_CHECK_INTERVAL: u64 = 2048;
+
+// size of the transposition table in megabytes when nothing else is asked for
+pub const DEFAULT_HASH_MB: usize = 16;
+
+// kept off the clock for the time it takes a move to get back to whoever asked for it
+const MOVE_OVERHEAD_MS: u64 = 50;
+
+// when nobody says how many moves are left until the next time control
+pub const DEFAULT_MOVES_TO_GO: u64 = 30;
+
+// no line gets searched deeper than this, quiescence included
+const MAX_PLY: usize = 128;
+
+// the deepest iteration worth asking for, a search limited only by time uses this as its depth
+pub const MAX_DEPTH: u32 = 64;
+
+// a capture that can't bring the score back up to alpha even with this much to spare isn't worth searching
+const DELTA_MARGIN: i32 = 200;
+
+// move ordering scores, the hash move first then captures by MVV-LVA, killers and finally quiet moves by history
+const HASH_MOVE_SCORE: i32 = 1_000_000;
+const CAPTURE_SCORE: i32 = 100_000;
+const KILLER_SCORES: [i32; 2] = [90_000, 80_000];
+
+#[derive(Debug, Copy, Clone)]
+pub struct SearchLimits {
+    pub depth: u32,
+    pub time: Option<Duration>,
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct SearchResult {
+    pub best_move: Option<Move>,
+    // centipawns from the side to move's point of view
+    pub score: i32,
+    pub depth: u32,
+    pub nodes: u64,
+}
+
+// how long to think with clock_ms left for moves_to_go moves, most of the increment goes on top
+pub fn time_for_move(clock_ms: u64, inc_ms: u64, moves_to_go: u64) -> Duration {
+    let ms = clock_ms / moves_to_go.max(1) + inc_ms * 3 / 4;
+    Duration::from_millis(ms.min(clock_ms.saturating_sub(MOVE_OVERHEAD_MS)).max(1))
+}
+
+pub struct Engine {
+    nodes: u64,
+    deadline: Option<Instant>,
+    stopped: bool,
+    // set from another thread to end the search early, the best move from the last finished iteration still comes back
+    stop: Arc<AtomicBool>,
+    // kept between searches so the next move starts with what was learnt on this one
+    tt: TranspositionTable,
+    // two quiet moves per ply that caused a beta cutoff in a sibling position
+    killers: Vec<[Option<Move>; 2]>,
+    // how often each quiet move has caused a cutoff weighted by depth, indexed by color, from and to square
+    history: Vec<i32>,
+}
+
+impl Engine {
+    pub fn new(hash_mb: usize) -> Self {
+        Engine {
+            nodes: 0,
+            deadline: None,
+            stopped: false,
+            stop: Arc::new(AtomicBool::new(false)),
+            tt: TranspositionTable::new(hash_mb),
+            killers: vec![],
+            history: vec![],
+        }
+    }
+
+    // the flag that stops a running search, it is never cleared by the engine so whoever sets it clears it again
+    pub fn stop_flag(&self) -> Arc<AtomicBool> {
+        self.stop.clone()
+    }
+
+    // throws away the transposition table for one of a different size
+    pub fn set_hash_size(&mut self, hash_mb: usize) {
+        self.tt = TranspositionTable::new(hash_mb);
+    }
+
+    // forgets everything learnt from earlier searches, for when a new game starts
+    pub fn clear(&mut self) {
+        self.tt.clear();
+    }
+
+    pub fn search(&mut self, board: &Board, limits: SearchLimits) -> SearchResult {
+        self.search_with(board, limits, |_| ())
+    }
+
+    // searches one ply deeper each time until the depth or time runs out, an unfinished iteration is thrown away
+    // report gets the result of every iteration that finishes
+    pub fn search_with(&mut self, board: &Board, limits: SearchLimits, mut report: impl FnMut(&SearchResult)) -> SearchResult {
+        let mut b = board.clone();
+        self.nodes = 0;
+        self.stopped = false;
+        self.deadline = limits.time.map(|t| Instant::now() + t);
+        let squares = (b.get_size() * b.get_size()) as usize;
+        self.killers = vec![[None; 2]; MAX_PLY];
+        self.history = vec![0; 2 * squares * squares];
+
+        let moves = b.legal_moves();
+        let mut result = SearchResult {
+            best_move: moves.first().copied(),
+            score: 0,
+            depth: 0,
+            nodes: 0,
+        };
+
+        // with nothing to play the game is already over, mated or stalemated, and there is nothing to search
+        if moves.is_empty() {
+            if b.in_check(b.get_turn()) {
+                result.score = -MATE;
+            }
+            report(&result);
+            return result;
+        }
+
+        for depth in 1..=limits.depth.max(1) {
+            let (score, best_move) = self.search_root(&mut b, depth, result.best_move);
+            if self.stopped {
+                break;
+            }
+
+            result = SearchResult {
+                best_move,
+                score,
+                depth,
+                nodes: self.nodes,
+            };
+            report(&result);
+
+            // no point looking deeper once a forced mate has been found
+            if score.abs() >= MATE - depth as i32 {
+                break;
+            }
+        }
+        result.nodes = self.nodes;
+        result
+    }
+
+    // the root is searched separately so it can hand back the best move and try last iteration's best move first
+    fn search_root(&mut self, b: &mut Board, depth: u32, previous_best: Option<Move>) -> (i32, Option<Move>) {
+        let mut moves = b.legal_moves();
+        if let Some(pos) = previous_best.and_then(|best| moves.iter().position(|m| *m == best)) {
+            moves.swap(0, pos);
+        }
+
+        let mut alpha = -INFINITY;
+        let mut best_move = moves.first().copied();
+        for m in moves {
+            b.make_move(m);
+            let score = -self.negamax(b, depth - 1, 1, -INFINITY, -alpha);
+            b.unmake_move();
+
+            if self.stopped {
+                break;
+            }
+            if score > alpha {
+                alpha = score;
+                best_move = Some(m);
+            }
+        }
+        (alpha, best_move)
+    }
+
+    fn check_time(&mut self) {
+        self.nodes += 1;
+        if self.nodes.is_multiple_of(TIME_CHECK_INTERVAL) {
+            if let Some(deadline) = self.deadline {
+                self.stopped |= Instant::now() >= deadline;
+            }
+            self.stopped |= self.stop.load(Ordering::Relaxed);
+        }
+    }
+
+    fn negamax(&mut self, b: &mut Board, depth: u32, ply: i32, mut alpha: i32, beta: i32) -> i32 {
+        self.check_time();
+        if self.stopped {
+            return 0;
+        }
+
+        // a repeated position is treated as a draw straight away since the side that repeated it can always repeat it again
+        if b.repetition_count() >= 2 || b.claimable_draw().is_some() || b.is_insufficient_material() {
+            return 0;
+        }
+        if depth == 0 || ply as usize >= MAX_PLY {
+            return self.quiescence(b, ply, alpha, beta);
+        }
+
+        // a result from at least as deep a search can end this one straight away if its bound allows it
+        let entry = self.tt.probe(b.hash(), ply);
+        if let Some(entry) = entry {
+            if entry.depth >= depth {
+                match entry.bound {
+                    Bound::Exact => return entry.score,
+                    Bound::Lower if entry.score >= beta => return entry.score,
+                    Bound::Upper if entry.score <= alpha => return entry.score,
+                    _ => (),
+                }
+            }
+        }
+
+        let mut moves = b.legal_moves();
+        if moves.is_empty() {
+            return match b.in_check(b.get_turn()) {
+                true => -MATE + ply,
+                false => 0,
+            };
+        }
+        self.order_moves(b, &mut moves, entry.and_then(|e| e.best_move), ply);
+
+        let original_alpha = alpha;
+        let mut best_move = None;
+        for m in moves {
+            b.make_move(m);
+            let score = -self.negamax(b, depth - 1, ply + 1, -beta, -alpha);
+            b.unmake_move();
+
+            if self.stopped {
+                return 0;
+            }
+            if score >= beta {
+                if !m.has(Move::CAPTURE) && m.promotion.is_none() {
+                    self.remember_cutoff(b, m, depth, ply);
+                }
+                self.tt.store(b.hash(), depth, Bound::Lower, beta, Some(m), ply);
+                return beta;
+            }
+            if score > alpha {
+                alpha = score;
+                best_move = Some(m);
+            }
+        }
+
+        let bound = match alpha > original_alpha {
+            true => Bound::Exact,
+            false => Bound::Upper,
+        };
+        self.tt.store(b.hash(), depth, bound, alpha, best_move, ply);
+        alpha
+    }
+
+    // only captures and promotions are searched past the horizon so the score isn't taken in the middle of an
+    // exchange, the side to move can always stand pat with the static evaluation instead unless it is in check
+    fn quiescence(&mut self, b: &mut Board, ply: i32, mut alpha: i32, beta: i32) -> i32 {
+        self.check_time();
+        if self.stopped {
+            return 0;
+        }
+
+        let in_check = b.in_check(b.get_turn());
+        let mut moves = b.legal_moves();
+        if in_check && moves.is_empty() {
+            return -MATE + ply;
+        }
+
+        let stand_pat = evaluate(b);
+        if ply as usize >= MAX_PLY {
+            return stand_pat;
+        }
+        if !in_check {
+            if stand_pat >= beta {
+                return beta;
+            }
+            alpha = alpha.max(stand_pat);
+            moves.retain(|m| m.has(Move::CAPTURE) || m.promotion.is_some());
+        }
+        self.order_moves(b, &mut moves, None, ply);
+
+        for m in moves {
+            if !in_check && m.promotion.is_none() {
+                // delta pruning, even winning the captured piece outright leaves the score short of alpha
+                if stand_pat + self.captured_value(b, m) + DELTA_MARGIN < alpha {
+                    continue;
+                }
+                // the capture loses material once every recapture is played out
+                if b.see(m) < 0 {
+                    continue;
+                }
+            }
+
+            b.make_move(m);
+            let score = -self.quiescence(b, ply + 1, -beta, -alpha);
+            b.unmake_move();
+
+            if self.stopped {
+                return 0;
+            }
+            if score >= beta {
+                return beta;
+            }
+            alpha = alpha.max(score);
+        }
+        alpha
+    }
+
+    fn captured_value(&self, b: &Board, m: Move) -> i32 {
+        match b.piece_at(m.to) {
+            Some(p) => piece_value(p.get_piecetype()),
+            // the pawn taken en passant sits beside the target square rather than on it
+            None if m.has(Move::EN_PASSANT) => piece_value(PieceType::Pawn),
+            None => 0,
+        }
+    }
+
+    // sorts the moves so the ones most likely to cause a cutoff are searched first
+    fn order_moves(&self, b: &Board, moves: &mut [Move], hash_move: Option<Move>, ply: i32) {
+        let killers = self.killers.get(ply as usize).copied().unwrap_or([None; 2]);
+        moves.sort_by_cached_key(|m| {
+            let score = if Some(*m) == hash_move {
+                HASH_MOVE_SCORE
+            }
+            else if m.has(Move::CAPTURE) || m.promotion.is_some() {
+                // most valuable victim first and for the same victim the least valuable attacker first
+                let victim = match b.piece_at(m.to) {
+                    Some(p) => piece_index(p.get_piecetype()) as i32 + 1,
+                    None if m.has(Move::EN_PASSANT) => 1,
+                    None => 0,
+                };
+                let attacker = piece_index(b.piece_at(m.from).unwrap().get_piecetype()) as i32;
+                let promotion = m.promotion.map_or(0, |p| piece_value(p) / 10);
+                CAPTURE_SCORE + victim * 10 - attacker + promotion
+            }
+            else if Some(*m) == killers[0] {
+                KILLER_SCORES[0]
+            }
+            else if Some(*m) == killers[1] {
+                KILLER_SCORES[1]
+            }
+            else {
+                self.history[self.history_index(b, *m)]
+            };
+            std::cmp::Reverse(score)
+        });
+    }
+
+    fn history_index(&self, b: &Board, m: Move) -> usize {
+        let squares = (b.get_size() * b.get_size()) as usize;
+        (color_index(b.get_turn()) * squares + m.from) * squares + m.to
+    }
+
+    // a quiet move that caused a cutoff becomes a killer for this ply and gains history
+    fn remember_cutoff(&mut self, b: &Board, m: Move, depth: u32, ply: i32) {
+        let killers = &mut self.killers[ply as usize];
+        if killers[0] != Some(m) {
+            killers[1] = killers[0];
+            killers[0] = Some(m);
+        }
+        let index = self.history_index(b, m);
+        self.history[index] += (depth * depth) as i32;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn best(fen: &str, depth: u32) -> (String, i32) {
+        let b = Board::from_fen(fen, 8).unwrap();
+        let result = Engine::new(1).search(&b, SearchLimits { depth, time: None });
+        (b.move_to_string(result.best_move.unwrap()), result.score)
+    }
+
+    #[test]
+    fn finds_mate_in_one() {
+        let (m, score) = best("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1", 3);
+        assert_eq!(m, "a1a8");
+        assert_eq!(score, MATE - 1);
+    }
+
+    #[test]
+    fn takes_a_hanging_queen() {
+        let (m, _) = best("4k3/8/8/3q4/8/8/3R4/4K3 w - - 0 1", 2);
+        assert_eq!(m, "d2d5");
+    }
+
+    #[test]
+    fn finds_mate_in_two() {
+        // the rook ladder, Ra7 then Rb8
+        let (_, score) = best("7k/8/8/8/8/8/R7/1R5K w - - 0 1", 4);
+        assert_eq!(score, MATE - 3);
+    }
+
+    #[test]
+    fn quiescence_sees_the_recapture() {
+        // at depth 1 the queen would happily take the pawn on d5 if it couldn't see exd5 coming back
+        let (m, _) = best("4k3/8/4p3/3p4/8/8/8/3QK3 w - - 0 1", 1);
+        assert_ne!(m, "d1d5");
+    }
+
+    #[test]
+    fn nothing_to_search_when_the_game_is_over() {
+        let b = Board::from_fen("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1", 8).unwrap();
+        let result = Engine::new(1).search(&b, SearchLimits { depth: 3, time: None });
+        assert_eq!((result.best_move, result.score, result.depth), (None, 0, 0));
+
+        let b = Board::from_fen("R5k1/5ppp/8/8/8/8/8/6K1 b - - 0 1", 8).unwrap();
+        let result = Engine::new(1).search(&b, SearchLimits { depth: 3, time: None });
+        assert_eq!((result.best_move, result.score, result.depth), (None, -MATE, 0));
+    }
+
+    #[test]
+    fn repeated_search_uses_the_transposition_table() {
+        let b = Board::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1", 8).unwrap();
+        let limits = SearchLimits { depth: 3, time: None };
+        let mut engine = Engine::new(1);
+        let first = engine.search(&b, limits);
+        let second = engine.search(&b, limits);
+        assert!(second.nodes < first.nodes);
+        assert_eq!(first.score, second.score);
+    }
+}
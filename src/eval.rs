@@ -0,0 +1,355 @@
+// a tapered evaluation, every term has a middlegame and an endgame score and the two are blended by how much
+// material is left on the board
+use crate::bitboard::{color_index, piece_index};
+use crate::board::*;
+
+// indexed by bitboard::piece_index, pawn knight bishop rook queen king
+const MATERIAL_MG: [i32; 6] = [82, 337, 365, 477, 1025, 0];
+const MATERIAL_EG: [i32; 6] = [94, 281, 297, 512, 936, 0];
+
+// how much each piece counts towards the game phase, a full set of pieces adds up to MAX_PHASE
+const PHASE: [i32; 6] = [0, 1, 1, 2, 4, 0];
+const MAX_PHASE: i32 = 24;
+
+// piece square tables from light's side with a8 first like the mailbox, dark reads them with the rows flipped
+#[rustfmt::skip]
+const PAWN_MG: [i32; 64] = [
+      0,   0,   0,   0,   0,   0,   0,   0,
+     50,  50,  50,  50,  50,  50,  50,  50,
+     10,  10,  20,  30,  30,  20,  10,  10,
+      5,   5,  10,  25,  25,  10,   5,   5,
+      0,   0,   0,  20,  20,   0,   0,   0,
+      5,  -5, -10,   0,   0, -10,  -5,   5,
+      5,  10,  10, -20, -20,  10,  10,   5,
+      0,   0,   0,   0,   0,   0,   0,   0,
+];
+
+#[rustfmt::skip]
+const PAWN_EG: [i32; 64] = [
+      0,   0,   0,   0,   0,   0,   0,   0,
+     60,  60,  60,  60,  60,  60,  60,  60,
+     40,  40,  40,  40,  40,  40,  40,  40,
+     25,  25,  25,  25,  25,  25,  25,  25,
+     15,  15,  15,  15,  15,  15,  15,  15,
+      5,   5,   5,   5,   5,   5,   5,   5,
+      0,   0,   0,   0,   0,   0,   0,   0,
+      0,   0,   0,   0,   0,   0,   0,   0,
+];
+
+#[rustfmt::skip]
+const KNIGHT: [i32; 64] = [
+    -50, -40, -30, -30, -30, -30, -40, -50,
+    -40, -20,   0,   0,   0,   0, -20, -40,
+    -30,   0,  10,  15,  15,  10,   0, -30,
+    -30,   5,  15,  20,  20,  15,   5, -30,
+    -30,   0,  15,  20,  20,  15,   0, -30,
+    -30,   5,  10,  15,  15,  10,   5, -30,
+    -40, -20,   0,   5,   5,   0, -20, -40,
+    -50, -40, -30, -30, -30, -30, -40, -50,
+];
+
+#[rustfmt::skip]
+const BISHOP: [i32; 64] = [
+    -20, -10, -10, -10, -10, -10, -10, -20,
+    -10,   0,   0,   0,   0,   0,   0, -10,
+    -10,   0,   5,  10,  10,   5,   0, -10,
+    -10,   5,   5,  10,  10,   5,   5, -10,
+    -10,   0,  10,  10,  10,  10,   0, -10,
+    -10,  10,  10,  10,  10,  10,  10, -10,
+    -10,   5,   0,   0,   0,   0,   5, -10,
+    -20, -10, -10, -10, -10, -10, -10, -20,
+];
+
+#[rustfmt::skip]
+const ROOK: [i32; 64] = [
+      0,   0,   0,   0,   0,   0,   0,   0,
+      5,  10,  10,  10,  10,  10,  10,   5,
+     -5,   0,   0,   0,   0,   0,   0,  -5,
+     -5,   0,   0,   0,   0,   0,   0,  -5,
+     -5,   0,   0,   0,   0,   0,   0,  -5,
+     -5,   0,   0,   0,   0,   0,   0,  -5,
+     -5,   0,   0,   0,   0,   0,   0,  -5,
+      0,   0,   0,   5,   5,   0,   0,   0,
+];
+
+#[rustfmt::skip]
+const QUEEN: [i32; 64] = [
+    -20, -10, -10,  -5,  -5, -10, -10, -20,
+    -10,   0,   0,   0,   0,   0,   0, -10,
+    -10,   0,   5,   5,   5,   5,   0, -10,
+     -5,   0,   5,   5,   5,   5,   0,  -5,
+     -5,   0,   5,   5,   5,   5,   0,  -5,
+    -10,   0,   5,   5,   5,   5,   0, -10,
+    -10,   0,   0,   0,   0,   0,   0, -10,
+    -20, -10, -10,  -5,  -5, -10, -10, -20,
+];
+
+// the king hides behind its pawns while there are pieces around and walks to the middle once they are gone
+#[rustfmt::skip]
+const KING_MG: [i32; 64] = [
+    -30, -40, -40, -50, -50, -40, -40, -30,
+    -30, -40, -40, -50, -50, -40, -40, -30,
+    -30, -40, -40, -50, -50, -40, -40, -30,
+    -30, -40, -40, -50, -50, -40, -40, -30,
+    -20, -30, -30, -40, -40, -30, -30, -20,
+    -10, -20, -20, -20, -20, -20, -20, -10,
+     20,  20,   0,   0,   0,   0,  20,  20,
+     20,  30,  10,   0,   0,  10,  30,  20,
+];
+
+#[rustfmt::skip]
+const KING_EG: [i32; 64] = [
+    -50, -40, -30, -20, -20, -30, -40, -50,
+    -30, -20, -10,   0,   0, -10, -20, -30,
+    -30, -10,  20,  30,  30,  20, -10, -30,
+    -30, -10,  30,  40,  40,  30, -10, -30,
+    -30, -10,  30,  40,  40,  30, -10, -30,
+    -30, -10,  20,  30,  30,  20, -10, -30,
+    -30, -30,   0,   0,   0,   0, -30, -30,
+    -50, -30, -30, -30, -30, -30, -30, -50,
+];
+
+const TABLES_MG: [&[i32; 64]; 6] = [&PAWN_MG, &KNIGHT, &BISHOP, &ROOK, &QUEEN, &KING_MG];
+const TABLES_EG: [&[i32; 64]; 6] = [&PAWN_EG, &KNIGHT, &BISHOP, &ROOK, &QUEEN, &KING_EG];
+
+// pawn structure, the passed pawn bonus is indexed by how far up the board the pawn is with 0 its own back rank
+const DOUBLED_MG: i32 = -10;
+const DOUBLED_EG: i32 = -20;
+const ISOLATED_MG: i32 = -10;
+const ISOLATED_EG: i32 = -15;
+const PASSED_MG: [i32; 8] = [0, 5, 5, 10, 20, 35, 60, 0];
+const PASSED_EG: [i32; 8] = [0, 10, 15, 25, 45, 70, 110, 0];
+
+// per square a piece can move to, counted from about how many squares it usually has so an average piece scores 0
+const MOBILITY_MG: [i32; 6] = [0, 4, 5, 2, 1, 0];
+const MOBILITY_EG: [i32; 6] = [0, 4, 5, 4, 2, 0];
+const MOBILITY_AVERAGE: [i32; 6] = [0, 4, 7, 7, 14, 0];
+
+// king safety only counts in the middlegame, once the queens are off the king is meant to come out
+const SHIELD_NEAR: i32 = 12;
+const SHIELD_FAR: i32 = 6;
+const KING_ZONE_ATTACK: i32 = -10;
+
+// a rough value for exchanges and move ordering, the king is worth more than everything else put together so
+// winning it always comes first
+pub fn piece_value(piece: PieceType) -> i32 {
+    match piece {
+        PieceType::King => 20_000,
+        _ => MATERIAL_MG[piece_index(piece)],
+    }
+}
+
+// centipawns from the side to move's point of view
+pub fn evaluate(board: &Board) -> i32 {
+    let size = board.get_size();
+
+    let mut mg = [0; 2];
+    let mut eg = [0; 2];
+    let mut phase = 0;
+    for pos in 0..(size * size) as usize {
+        let p = match board.piece_at(pos) {
+            Some(p) => p,
+            None => continue,
+        };
+        let c = color_index(p.get_color());
+        let i = piece_index(p.get_piecetype());
+        let sq = table_square(size, pos, p.get_color());
+
+        mg[c] += MATERIAL_MG[i] + TABLES_MG[i][sq];
+        eg[c] += MATERIAL_EG[i] + TABLES_EG[i][sq];
+        phase += PHASE[i];
+
+        if MOBILITY_AVERAGE[i] > 0 {
+            let mobility = board.mobility(pos) as i32 - MOBILITY_AVERAGE[i];
+            mg[c] += MOBILITY_MG[i] * mobility;
+            eg[c] += MOBILITY_EG[i] * mobility;
+        }
+    }
+
+    for color in [Color::Light, Color::Dark] {
+        let c = color_index(color);
+        let (pawns_mg, pawns_eg) = pawn_structure(board, color);
+        mg[c] += pawns_mg + king_safety(board, color);
+        eg[c] += pawns_eg;
+    }
+
+    // bigger boards can hold more than a full set of pieces, they still count as the middlegame
+    let phase = phase.min(MAX_PHASE);
+    let score = ((mg[0] - mg[1]) * phase + (eg[0] - eg[1]) * (MAX_PHASE - phase)) / MAX_PHASE;
+    match board.get_turn() {
+        Color::Light => score,
+        Color::Dark => -score,
+    }
+}
+
+// the tables are 8*8 so other board sizes are squashed or stretched onto them
+fn table_square(size: i32, pos: usize, color: Color) -> usize {
+    let size = size as usize;
+    let row = match color {
+        Color::Light => pos / size,
+        Color::Dark => size - 1 - pos / size,
+    };
+    row * 8 / size * 8 + pos % size * 8 / size
+}
+
+// how far up the board a row is for color on the 0 to 7 scale of the passed pawn tables
+fn relative_rank(size: i32, row: i32, color: Color) -> usize {
+    let advanced = match color {
+        Color::Light => size - 1 - row,
+        Color::Dark => row,
+    };
+    (advanced * 7 / (size - 1).max(1)) as usize
+}
+
+// doubled, isolated and passed pawns of color as a middlegame and endgame score
+fn pawn_structure(board: &Board, color: Color) -> (i32, i32) {
+    let size = board.get_size();
+    let pawn = Piece::from_type(PieceType::Pawn, color);
+    let enemy_pawn = Piece::from_type(PieceType::Pawn, color.opposite());
+
+    let mut own = vec![];
+    let mut enemy = vec![];
+    for pos in 0..(size * size) as usize {
+        let square = (pos as i32 / size, pos as i32 % size);
+        match board.piece_at(pos) {
+            Some(p) if p == pawn => own.push(square),
+            Some(p) if p == enemy_pawn => enemy.push(square),
+            _ => (),
+        }
+    }
+
+    let mut files = vec![0; size as usize];
+    for (_, col) in &own {
+        files[*col as usize] += 1;
+    }
+
+    let (mut mg, mut eg) = (0, 0);
+    for file in &files {
+        if *file > 1 {
+            mg += DOUBLED_MG * (file - 1);
+            eg += DOUBLED_EG * (file - 1);
+        }
+    }
+
+    for (row, col) in &own {
+        let neighbours = (col - 1..=col + 1)
+            .filter(|c| *c != *col && *c >= 0 && *c < size)
+            .map(|c| files[c as usize])
+            .sum::<i32>();
+        if neighbours == 0 {
+            mg += ISOLATED_MG;
+            eg += ISOLATED_EG;
+        }
+
+        // passed when no enemy pawn is in front of it on its own file or either side
+        let passed = !enemy.iter().any(|(r, c)| {
+            (c - col).abs() <= 1
+                && match color {
+                    Color::Light => r < row,
+                    Color::Dark => r > row,
+                }
+        });
+        if passed {
+            let rank = relative_rank(size, *row, color);
+            mg += PASSED_MG[rank];
+            eg += PASSED_EG[rank];
+        }
+    }
+    (mg, eg)
+}
+
+// pawns in front of the king and enemy attacks on the squares around it, only used in the middlegame
+fn king_safety(board: &Board, color: Color) -> i32 {
+    let size = board.get_size();
+    let king = Piece::from_type(PieceType::King, color);
+    let pos = match (0..(size * size) as usize).find(|pos| board.piece_at(*pos) == Some(king)) {
+        Some(pos) => pos as i32,
+        None => return 0,
+    };
+    let (row, col) = (pos / size, pos % size);
+    let forward = match color {
+        Color::Light => -1,
+        Color::Dark => 1,
+    };
+    let pawn = Some(Piece::from_type(PieceType::Pawn, color));
+
+    let mut score = 0;
+    for dc in -1..=1 {
+        let c = col + dc;
+        if c < 0 || c >= size {
+            continue;
+        }
+        for (distance, bonus) in [(1, SHIELD_NEAR), (2, SHIELD_FAR)] {
+            let r = row + forward * distance;
+            if r >= 0 && r < size && board.piece_at((r * size + c) as usize) == pawn {
+                score += bonus;
+            }
+        }
+    }
+
+    for dr in -1..=1 {
+        for dc in -1..=1 {
+            let (r, c) = (row + dr, col + dc);
+            if (dr, dc) != (0, 0)
+                && r >= 0
+                && r < size
+                && c >= 0
+                && c < size
+                && board.is_square_attacked((r * size + c) as usize, color.opposite())
+            {
+                score += KING_ZONE_ATTACK;
+            }
+        }
+    }
+    score
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval(fen: &str, size: i32) -> i32 {
+        evaluate(&Board::from_fen(fen, size).unwrap())
+    }
+
+    #[test]
+    fn start_position_is_level() {
+        assert_eq!(eval("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", 8), 0);
+        assert_eq!(eval("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR b KQkq - 0 1", 8), 0);
+        assert_eq!(eval("1rnbqkbnr1/1pppppppp1/10/10/10/10/10/10/1PPPPPPPP1/1RNBQKBNR1 w - - 0 1", 10), 0);
+    }
+
+    #[test]
+    fn mirrored_positions_score_the_same_for_the_side_to_move() {
+        let light = eval("r1bqk2r/ppp2ppp/2n5/3pP3/1b1P4/2N2N2/PP3PPP/R2QKB1R w KQkq - 0 1", 8);
+        let dark = eval("r2qkb1r/pp3ppp/2n2n2/1B1p4/3Pp3/2N5/PPP2PPP/R1BQK2R b KQkq - 0 1", 8);
+        assert_eq!(light, dark);
+    }
+
+    #[test]
+    fn extra_material_is_better() {
+        assert!(eval("4k3/8/8/8/8/8/8/3QK3 w - - 0 1", 8) > 800);
+        assert!(eval("4k3/8/8/8/8/8/8/3QK3 b - - 0 1", 8) < -800);
+    }
+
+    #[test]
+    fn pawn_structure_terms() {
+        // a doubled isolated pair on the c file against a connected pair
+        let b = Board::from_fen("4k3/8/8/8/8/2P5/2P5/4K3 w - - 0 1", 8).unwrap();
+        let (mg, eg) = pawn_structure(&b, Color::Light);
+        assert_eq!(mg, DOUBLED_MG + 2 * ISOLATED_MG + PASSED_MG[1] + PASSED_MG[2]);
+        assert_eq!(eg, DOUBLED_EG + 2 * ISOLATED_EG + PASSED_EG[1] + PASSED_EG[2]);
+
+        // the d pawn is stopped by the e pawn beside it so only the connected a and b pawns are passed
+        let b = Board::from_fen("4k3/8/4p3/8/PP1P4/8/8/4K3 w - - 0 1", 8).unwrap();
+        let (mg, _) = pawn_structure(&b, Color::Light);
+        assert_eq!(mg, ISOLATED_MG + 2 * PASSED_MG[3]);
+    }
+
+    #[test]
+    fn a_sheltered_king_is_safer() {
+        let sheltered = Board::from_fen("4k3/8/8/8/8/8/5PPP/6K1 w - - 0 1", 8).unwrap();
+        let open = Board::from_fen("4k3/8/8/8/8/5PPP/8/6K1 w - - 0 1", 8).unwrap();
+        assert!(king_safety(&sheltered, Color::Light) > king_safety(&open, Color::Light));
+    }
+}
@@ -4,11 +4,10 @@ use piston::BuildFromWindowSettings;
 
 use opengl_graphics::{GlGraphics, OpenGL, Texture, TextureSettings};
 use piston::input::*;
-use std::collections::HashSet;
 use std::path::Path;
 use piston::event_loop::*;
 
-use crate::board::*;
+use chess::board::*;
 use crate::nqueens::n_queens_fen;
 
 pub fn init_game_window(opengl:OpenGL, initial_window_size: u32) -> GlutinWindow {
@@ -49,7 +48,7 @@ impl Game {
     }
 
     // actually draws the board
-    pub fn board(&mut self, board: &Board, arg: &RenderArgs, possible_moves: &HashSet<i32>, prev: i32, post: i32) {
+    pub fn board(&mut self, board: &Board, arg: &RenderArgs, possible_moves: &[Move], prev: i32, post: i32) {
         // get rgb value divide by 255 to get float representation
         // let white:[f32;4] = [1.0, 1.0, 1.0, 1.0];
         // let black:[f32;4] = [0.0, 0.0, 0.0, 1.0];
@@ -71,7 +70,7 @@ impl Game {
 
                 let point = i + j * c;
                 let color: [f32; 4];
-                if possible_moves.contains(&((j * c) + i)) {
+                if possible_moves.iter().any(|m| m.to as i32 == point) {
                     color = orange;
                 }
                 else if point == prev || point == post {
@@ -119,31 +118,65 @@ impl Game {
         }
     }
 
-    pub fn move_piece(&mut self, board: &mut Board, possible_moves:&HashSet<i32>,old_pos: [f64; 2], new_pos: [f64; 2], turn: &mut Color) -> [i32; 2] {
-        // if coords are less than the size of the window
-        if old_pos[0] < 0.0
-            || old_pos[1] < 0.0
-            || new_pos[0] < 0.0
-            || new_pos[1] < 0.0
-            || new_pos[0] > self.win_size as f64
-        {
-            return [-1, -1];
-        }
-
-        let board_size = board.get_size();
-        let square_size = self.win_size / board_size;
+    // dims the whole board once the game has ended, tinted towards the winner's color
+    pub fn draw_result(&mut self, status: GameStatus, arg: &RenderArgs) {
+        let tint: [f32; 4] = match status {
+            GameStatus::Ongoing => return,
+            GameStatus::Checkmate(Color::Light) => [1.0, 1.0, 1.0, 0.45],
+            GameStatus::Checkmate(Color::Dark) => [0.0, 0.0, 0.0, 0.45],
+            GameStatus::Stalemate | GameStatus::Draw(_) => [0.5, 0.5, 0.5, 0.45],
+        };
+
+        let square = graphics::rectangle::square(0.0, 0.0, self.win_size as f64);
+        self.gl.draw(arg.viewport(), |c, gl| {
+            graphics::rectangle(tint, square, c.transform, gl);
+        });
+    }
 
-        let o_pos =
-            old_pos[0] as i32 / square_size + (old_pos[1] as i32 / square_size) * board_size;
-        let n_pos =
-            new_pos[0] as i32 / square_size + (new_pos[1] as i32 / square_size) * board_size;
+    // the squares the promotion chooser covers, starting on the promotion square and heading towards the middle of the board
+    fn promotion_squares(&self, board: &Board, square: usize) -> Vec<usize> {
+        let s = board.get_size() as usize;
+        let step_down = square / s == 0;
+        (0..PROMOTION_PIECES.len().min(s))
+            .map(|i| match step_down {
+                true => square + i * s,
+                false => square - i * s,
+            })
+            .collect()
+    }
 
-        match board.move_piece(o_pos as usize, n_pos as usize, possible_moves, turn) {
-            true => [o_pos, n_pos],
-            false => [-1, -1],
+    // draws the pieces a pawn can promote to on top of the board
+    pub fn draw_promotion(&mut self, board: &Board, arg: &RenderArgs, square: usize, color: Color) {
+        let white: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+        let s = board.get_size() as usize;
+        let square_size: f64 = (self.win_size / s as i32) as f64;
+
+        for (sq, piece) in self.promotion_squares(board, square).into_iter().zip(PROMOTION_PIECES) {
+            let rect = graphics::rectangle::square(
+                square_size * (sq % s) as f64,
+                square_size * (sq / s) as f64,
+                square_size,
+            );
+            let img = graphics::Image::new().rect(rect);
+            let piece_image = self.piece_textures.get(&Piece::from_type(piece, color).get_id()).unwrap();
+
+            self.gl.draw(arg.viewport(), |c, gl| {
+                graphics::rectangle(white, rect, c.transform, gl);
+                img.draw(piece_image, &graphics::DrawState::new_alpha(), c.transform, gl);
+            });
         }
     }
 
+    // gives back the piece the cursor is over in the promotion chooser or None if it missed the chooser
+    pub fn get_promotion_choice(&self, board: &Board, square: usize, cursor_pos: [f64; 2]) -> Option<PieceType> {
+        let clicked = self.get_board_pos_from_cursor(board, cursor_pos)?;
+        self.promotion_squares(board, square)
+            .into_iter()
+            .zip(PROMOTION_PIECES)
+            .find(|(sq, _)| *sq == clicked)
+            .map(|(_, piece)| piece)
+    }
+
     pub fn get_board_pos_from_cursor(&self, board: &Board, cursor_pos: [f64; 2]) -> Option<usize> {
         if cursor_pos[0] < 0.0
             || cursor_pos[1] < 0.0
@@ -166,19 +199,17 @@ impl Game {
     }
 
     pub fn n_queens(&mut self, game_window: &mut GlutinWindow, board_size:i32, time_till_switch:u64) {
-        //let mut b: Board = Board::new("".to_string(), board_size);
+        //let mut b: Board = Board::diagram_from_fen("", board_size).unwrap();
         let mut event = Events::new(EventSettings::new());
 
         let queen_fens = n_queens_fen(board_size);
-        let boards:Vec<Board> = queen_fens.into_iter().map(|x| Board::new(x, board_size)).collect();
+        let boards:Vec<Board> = queen_fens.iter().filter_map(|x| Board::diagram_from_fen(x, board_size).ok()).collect();
         let mut boards_iter = boards.iter();
 
-        let tmp = HashSet::new();
-
         while let Some(e) = event.next(game_window) {
             if let Some(r) = e.render_args() {
                 if let Some(item) = boards_iter.next() {
-                    self.board(item,&r, &tmp, -1, -1);
+                    self.board(item,&r, &[], -1, -1);
                     self.draw_pieces(item,&r);
                     std::thread::sleep(std::time::Duration::from_millis(time_till_switch));
                 }
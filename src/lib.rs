@@ -0,0 +1,12 @@
+// everything that doesn't need a window lives in the library so the gui and the protocol front ends share it
+pub mod bitboard;
+pub mod board;
+pub mod engine;
+pub mod eval;
+pub mod perft;
+pub mod pgn;
+pub mod tt;
+pub mod uci;
+pub mod uci_client;
+pub mod xboard;
+pub mod zobrist;
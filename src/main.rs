@@ -4,18 +4,135 @@ extern crate opengl_graphics;
 extern crate piston;
 
 use game::init_game_window;
+use glutin_window::GlutinWindow;
 use opengl_graphics::{GlGraphics, OpenGL};
 use piston::event_loop::*;
 use piston::input::*;
-use std::collections::HashSet;
+use piston::window::AdvancedWindow;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
 
-mod board;
-mod game;
-use crate::board::*;
+use chess::board::*;
+use chess::pgn::{PgnGame, Tags};
+use chess::uci_client::{EngineMessage, ExternalEngine, Score};
+use chess::{engine, perft};
 
+mod game;
 mod nqueens;
 
 fn main() {
+    // `chess perft <fen> <depth>` checks the move generator from the command line without opening a window
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().map(|a| a.as_str()) == Some("perft") {
+        if let Err(e) = perft::run(&args[1..]) {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    // `chess --replay <file> [game]` steps through a game from a pgn file, the first one unless a number is given
+    let replay = match args.iter().position(|a| a == "--replay") {
+        Some(i) => match args.get(i + 1) {
+            Some(path) => {
+                let number = args.get(i + 2).and_then(|n| n.parse().ok()).unwrap_or(1);
+                match load_replay(path, number) {
+                    Ok(game) => Some(game),
+                    Err(e) => {
+                        eprintln!("{}", e);
+                        std::process::exit(1);
+                    }
+                }
+            }
+            None => {
+                eprintln!("usage: chess --replay <file> [game]");
+                std::process::exit(1);
+            }
+        },
+        None => None,
+    };
+
+    // `chess --vs-engine <light|dark>` has the computer play that color
+    let engine_color = match args.iter().position(|a| a == "--vs-engine") {
+        Some(i) => match args.get(i + 1).map(|a| a.as_str()) {
+            Some("light") | Some("white") => Some(Color::Light),
+            Some("dark") | Some("black") => Some(Color::Dark),
+            _ => {
+                eprintln!("usage: chess --vs-engine <light|dark>");
+                std::process::exit(1);
+            }
+        },
+        None => None,
+    };
+    // a replayed game only gets looked at so the engine never plays in one
+    let engine_color = engine_color.filter(|_| replay.is_none());
+    let engine_limits = engine::SearchLimits {
+        depth: 64,
+        time: Some(std::time::Duration::from_millis(1000)),
+    };
+    // `--hash <mb>` sets how much memory the engine's transposition table gets
+    let hash_mb = match args.iter().position(|a| a == "--hash") {
+        Some(i) => match args.get(i + 1).and_then(|a| a.parse().ok()) {
+            Some(mb) => mb,
+            None => {
+                eprintln!("usage: chess --hash <megabytes>");
+                std::process::exit(1);
+            }
+        },
+        None => engine::DEFAULT_HASH_MB,
+    };
+    let engine = engine::Engine::new(hash_mb);
+    let stop = engine.stop_flag();
+    let engine = Arc::new(Mutex::new(engine));
+    // the built in engine's search while it is thinking
+    let mut search: Option<JoinHandle<engine::SearchResult>> = None;
+
+    // `--uci-engine <path>` hands the thinking to another engine, it plays the --vs-engine color if there is one
+    // and otherwise analyses every position on the board
+    let mut external = match args.iter().position(|a| a == "--uci-engine") {
+        Some(i) => match args.get(i + 1).map(|path| (path, ExternalEngine::spawn(path))) {
+            Some((_, Ok(external))) => {
+                println!("using {}", external.get_name());
+                Some(external)
+            }
+            Some((path, Err(e))) => {
+                eprintln!("could not start {}: {}", path, e);
+                std::process::exit(1);
+            }
+            None => {
+                eprintln!("usage: chess --uci-engine <path>");
+                std::process::exit(1);
+            }
+        },
+        None => None,
+    };
+    let analysing = external.is_some() && engine_color.is_none();
+
+    // `--pgn <path>` is where the game gets saved, by default a new file named after when the window was opened
+    let pgn_path = match args.iter().position(|a| a == "--pgn") {
+        Some(i) => match args.get(i + 1) {
+            Some(path) => path.clone(),
+            None => {
+                eprintln!("usage: chess --pgn <path>");
+                std::process::exit(1);
+            }
+        },
+        None => {
+            let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap_or_default();
+            format!("chess-{}.pgn", now.as_secs())
+        }
+    };
+    let player_name = |color: Color| match (Some(color) == engine_color, &external) {
+        (true, Some(external)) => external.get_name().to_string(),
+        (true, None) => "chess".to_string(),
+        (false, _) => "Player".to_string(),
+    };
+    let tags = Tags::new(&player_name(Color::Light), &player_name(Color::Dark));
+    // the hash of the position the external engine is searching, cleared once that search is stopped or has answered
+    // so coming back to the same position after an undo asks again
+    let mut asked: Option<u64> = None;
+
     let opengl = OpenGL::V4_5;
 
     // the window_size should be cleanly divisible by board_size otherwise there may be blank pixels on the edges of the screen
@@ -23,12 +140,13 @@ fn main() {
     let board_size = 8_i32;
 
     // the starting board as a fen string along with some other example fen strings
-    let fen = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR";
+    // fields after the piece placement are optional and default to light to move with castling inferred from the pieces
+    let fen = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
     //let fen = "rnbqkbnr/pppppppp/////PPPPPPPP/RNBQKBNR";
     //let fen = "r1b1k1nr/p2p1pNp/n2B4/1p1NP2P/6P1/3P1Q2/P1P1K3/q5b1";
     //let fen = "8/5k2/3p4/1p1Pp2p/pP2Pp1P/P4P1K/8/8";
     //let fen = "///////";
-    //let fen = "b/r5n//q2k//Q2K/R5N/B6B";
+    //let fen = "b7/r5n1//q2k4//Q2K4/R5N1/B6B";
 
     // this does a different event loop where the window shows different solutions to the n queens problem
     let n_queens = false;
@@ -42,17 +160,42 @@ fn main() {
         return ;
     }
 
-    let mut b = Board::new(fen.to_string(), board_size);
+    let mut b = match &replay {
+        // the whole game is played and taken back again so the right arrow can redo it one move at a time
+        Some(game) => {
+            let mut b = game.start_board();
+            for m in &game.moves {
+                b.make_move(*m);
+            }
+            while b.unmake_move() {}
+            b
+        }
+        None => match Board::from_fen(fen, board_size) {
+            Ok(b) => b,
+            Err(e) => {
+                eprintln!("could not load fen \"{}\": {}", fen, e);
+                return;
+            }
+        },
+    };
     let mut event = Events::new(EventSettings::new());
-    event.set_lazy(true);
+    // the engine moves between frames so the window has to keep drawing even without any input
+    event.set_lazy(engine_color.is_none() && external.is_none());
 
     let mut prev: i32 = -1;
     let mut post: i32 = -1;
-    let mut turn = Color::Light;
+    let mut status = b.game_status();
+    show_status(&mut game_window, &b, status);
+    if let Some(game) = &replay {
+        show_replay(&mut game_window, &b, game, status);
+    }
 
     let mut last_cursor_pos = [-1.0, -1.0];
     let mut start_cursor_pos = [-1.0, -1.0];
-    let mut possible_moves: HashSet<i32> = HashSet::new();
+    let mut possible_moves: Vec<Move> = vec![];
+    // the from and to squares of a pawn move waiting on the player to pick a promotion piece
+    let mut promotion: Option<(usize, usize)> = None;
+    let mut ctrl_held = false;
 
     // the main game loop
     while let Some(e) = event.next(&mut game_window) {
@@ -66,49 +209,318 @@ fn main() {
         if let Some(r) = e.render_args() {
             game.board(&b, &r, &possible_moves, prev, post);
             game.draw_pieces(&b, &r);
+            if let Some((_, n_pos)) = promotion {
+                game.draw_promotion(&b, &r, n_pos, b.get_turn());
+            }
+            game.draw_result(status, &r);
         }
 
+        // backspace or ctrl+z takes a move back and ctrl+y plays it again, this also works after the game has ended
+        // when replaying a game the left and right arrows step through it
         if let Some(r) = e.button_args() {
-            // if user left clicks
-            if r.button == Button::Mouse(MouseButton::Left) && r.state == ButtonState::Press {
-                start_cursor_pos = last_cursor_pos;
+            if let Button::Keyboard(key) = r.button {
+                if key == Key::LCtrl || key == Key::RCtrl {
+                    ctrl_held = r.state == ButtonState::Press;
+                }
 
-                // grab board position that the user clicked on
-                if let Some(v) = game.get_board_pos_from_cursor(&b, last_cursor_pos) {
-                    if possible_moves.contains(&(v as i32)) {
-                        let points = game.move_piece(&mut b, &possible_moves, start_cursor_pos, last_cursor_pos, &mut turn);
+                // p saves the game so far, it is saved again when the window closes
+                if r.state == ButtonState::Press && key == Key::P {
+                    save_pgn(&pgn_path, &b, &tags, status);
+                }
 
-                        // this handles the yellow background on the piece that just moved
-                        if points[0] != points[1] {
-                            prev = points[0];
-                            post = points[1];
-                        }
+                // d claims a threefold repetition or fifty move draw once one is available
+                if r.state == ButtonState::Press && key == Key::D && status == GameStatus::Ongoing {
+                    if let Some(reason) = b.claimable_draw() {
+                        status = GameStatus::Draw(reason);
+                        possible_moves.clear();
+                        promotion = None;
+                        println!("{}", status);
+                        show_status(&mut game_window, &b, status);
                     }
-                    possible_moves = b.get_valid_moves(v);
+                }
+
+                let changed = match (r.state, key) {
+                    (ButtonState::Press, Key::Backspace) => b.unmake_move(),
+                    (ButtonState::Press, Key::Z) if ctrl_held => b.unmake_move(),
+                    (ButtonState::Press, Key::Y) if ctrl_held => b.redo_move(),
+                    (ButtonState::Press, Key::Left) if replay.is_some() => b.unmake_move(),
+                    (ButtonState::Press, Key::Right) if replay.is_some() => b.redo_move(),
+                    _ => false,
                 };
+                // against the engine moves are taken back and replayed in pairs so it is the player's turn again
+                if changed && Some(b.get_turn()) == engine_color {
+                    match key {
+                        Key::Y => b.redo_move(),
+                        _ => b.unmake_move(),
+                    };
+                }
+                if changed {
+                    stop_search(&mut search, &stop);
+                    (prev, post) = match b.last_move() {
+                        Some(m) => (m.from as i32, m.to as i32),
+                        None => (-1, -1),
+                    };
+                    possible_moves.clear();
+                    promotion = None;
+                    status = b.game_status();
+                    show_status(&mut game_window, &b, status);
+                    if let Some(game) = &replay {
+                        show_replay(&mut game_window, &b, game, status);
+                    }
+                    println!("{}", b.to_fen());
+                }
             }
-            // if user stops left clicking
-            if r.button == Button::Mouse(MouseButton::Left) && r.state == ButtonState::Release {
-                let points = game.move_piece(&mut b, &possible_moves, start_cursor_pos, last_cursor_pos, &mut turn);
+        }
 
-                // this handles the yellow background on the piece that just moved
-                if points[0] != points[1] {
-                    prev = points[0];
-                    post = points[1];
-                    possible_moves = HashSet::new();
+        let mut engine_move = None;
+
+        // the built in engine thinks on its own thread so the window keeps drawing, its move is picked up once the
+        // search has finished and a search is stopped as soon as its position leaves the board
+        let engine_turn = status == GameStatus::Ongoing && Some(b.get_turn()) == engine_color;
+        if external.is_none() && engine_turn && search.is_none() {
+            stop.store(false, Ordering::Relaxed);
+            let board = b.clone();
+            let engine = engine.clone();
+            search = Some(thread::spawn(move || engine.lock().unwrap().search(&board, engine_limits)));
+        }
+        if !engine_turn {
+            stop_search(&mut search, &stop);
+        }
+        if search.as_ref().is_some_and(|s| s.is_finished()) {
+            let result = search.take().unwrap().join().unwrap();
+            if let Some(m) = result.best_move {
+                println!(
+                    "engine plays {} (depth {}, score {}, {} nodes)",
+                    b.move_to_string(m),
+                    result.depth,
+                    result.score,
+                    result.nodes
+                );
+                engine_move = Some(m);
+            }
+        }
+
+        // an external engine thinks in the background, a search of a position that is no longer on the board is
+        // stopped and its output is picked up every time round the loop
+        // set when the external engine has exited so it can be dropped once it is no longer borrowed
+        let mut external_lost = false;
+        if let Some(external) = &mut external {
+            let wanted = status == GameStatus::Ongoing && (analysing || Some(b.get_turn()) == engine_color);
+            if external.is_searching() && (!wanted || asked != Some(b.hash())) {
+                if let Err(e) = external.stop() {
+                    eprintln!("{}: {}", external.get_name(), e);
                 }
+                asked = None;
+            }
+            if wanted && !external.is_searching() && asked != Some(b.hash()) {
+                let movetime = match analysing {
+                    true => None,
+                    false => engine_limits.time,
+                };
+                if let Err(e) = external.go(&b, movetime) {
+                    eprintln!("{}: {}", external.get_name(), e);
+                }
+                asked = Some(b.hash());
+            }
 
-                println!("MOVE FROM {:?} TO {:?}", start_cursor_pos, last_cursor_pos);
-                start_cursor_pos = [-1.0, -1.0];
+            let messages = match external.poll() {
+                Ok(messages) => messages,
+                Err(e) => {
+                    eprintln!("{}: {}", external.get_name(), e);
+                    external_lost = true;
+                    vec![]
+                }
+            };
+            for message in messages {
+                // either way the search is over so the position gets sent again if it is still the engine's turn
+                if let EngineMessage::BestMove(_) = message {
+                    asked = None;
+                }
+                match message {
+                    EngineMessage::Info(info) if analysing && !info.pv.is_empty() => {
+                        game_window.set_title(analysis_title(&info));
+                    }
+                    EngineMessage::BestMove(mv) if !analysing => match b.move_from_string(&mv) {
+                        Some(m) => {
+                            println!("{} plays {}", external.get_name(), mv);
+                            engine_move = Some(m);
+                        }
+                        None => eprintln!("{} sent an illegal move {}", external.get_name(), mv),
+                    },
+                    _ => (),
+                }
+            }
+        }
+        // without its engine the game carries on, against the built in engine if there was an engine to play
+        if external_lost {
+            external = None;
+            if engine_color.is_some() {
+                println!("the built in engine takes over");
             }
         }
 
+        if let Some(m) = engine_move {
+            b.make_move(m);
+            prev = m.from as i32;
+            post = m.to as i32;
+            possible_moves.clear();
+            println!("{}", b.to_fen());
+
+            status = b.game_status();
+            if status != GameStatus::Ongoing {
+                println!("{}", status);
+            }
+            show_status(&mut game_window, &b, status);
+        }
+
+        // the cursor is tracked even while clicks are ignored so the next click lands where it points
         if let Some(cursor_args) = e.mouse_cursor_args() {
             last_cursor_pos = cursor_args;
         }
 
+        // once the game is over the board stays as it is and clicks are ignored, a replayed game can't be played on
+        // and the engine's pieces can't be moved while it is thinking
+        if status != GameStatus::Ongoing || replay.is_some() || Some(b.get_turn()) == engine_color {
+            continue;
+        }
+
+        if let Some(r) = e.button_args() {
+            // the from and to squares of a move the player is trying to make with this click
+            let mut attempt: Option<(usize, usize)> = None;
+
+            // if user left clicks
+            if r.button == Button::Mouse(MouseButton::Left) && r.state == ButtonState::Press {
+                // while the promotion chooser is up the click picks a piece, clicking anywhere else cancels the move
+                if let Some((o_pos, n_pos)) = promotion {
+                    if let Some(m) = game
+                        .get_promotion_choice(&b, n_pos, last_cursor_pos)
+                        .and_then(|piece| b.find_move(o_pos, n_pos, Some(piece)))
+                    {
+                        b.make_move(m);
+                        prev = o_pos as i32;
+                        post = n_pos as i32;
+                        println!("{}", b.to_fen());
+                    }
+                    promotion = None;
+                    possible_moves.clear();
+                }
+                else {
+                    start_cursor_pos = last_cursor_pos;
+
+                    // grab board position that the user clicked on, clicking a highlighted square moves the selected piece there
+                    if let Some(v) = game.get_board_pos_from_cursor(&b, last_cursor_pos) {
+                        match possible_moves.iter().find(|m| m.to == v) {
+                            Some(m) => attempt = Some((m.from, m.to)),
+                            None => possible_moves = b.get_valid_moves(v),
+                        }
+                    };
+                }
+            }
+            // if user stops left clicking
+            if r.button == Button::Mouse(MouseButton::Left) && r.state == ButtonState::Release {
+                let o_pos = game.get_board_pos_from_cursor(&b, start_cursor_pos);
+                let n_pos = game.get_board_pos_from_cursor(&b, last_cursor_pos);
+                if let (Some(o_pos), Some(n_pos)) = (o_pos, n_pos) {
+                    if possible_moves.iter().any(|m| m.from == o_pos && m.to == n_pos) {
+                        attempt = Some((o_pos, n_pos));
+                    }
+                }
+                start_cursor_pos = [-1.0, -1.0];
+            }
+
+            if let Some((o_pos, n_pos)) = attempt {
+                // a pawn reaching the last rank waits for the player to pick what it becomes
+                if b.is_promotion(o_pos, n_pos) {
+                    promotion = Some((o_pos, n_pos));
+                }
+                else if let Some(m) = b.find_move(o_pos, n_pos, None) {
+                    b.make_move(m);
+
+                    // this handles the yellow background on the piece that just moved
+                    prev = o_pos as i32;
+                    post = n_pos as i32;
+                    possible_moves.clear();
+                    println!("{}", b.to_fen());
+                }
+            }
+
+            if attempt.is_some() {
+                status = b.game_status();
+                if status != GameStatus::Ongoing {
+                    possible_moves.clear();
+                    println!("{}", status);
+                }
+                show_status(&mut game_window, &b, status);
+            }
+        }
+
         // if let Some(b) = e.mouse_relative_args() {
         //     println!("{:?}", b);
         // }
     }
+
+    stop_search(&mut search, &stop);
+    if b.last_move().is_some() && replay.is_none() {
+        save_pgn(&pgn_path, &b, &tags, status);
+    }
+}
+
+// ends the built in engine's search and throws its move away
+fn stop_search(search: &mut Option<JoinHandle<engine::SearchResult>>, stop: &AtomicBool) {
+    if let Some(search) = search.take() {
+        stop.store(true, Ordering::Relaxed);
+        search.join().unwrap();
+    }
+}
+
+// game number of the pgn file at path, counting from 1
+fn load_replay(path: &str, number: usize) -> Result<PgnGame, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("could not read {}: {}", path, e))?;
+    let mut games = chess::pgn::parse_pgn(&text).map_err(|e| format!("could not read {}: {}", path, e))?;
+    match number {
+        n if n >= 1 && n <= games.len() => Ok(games.swap_remove(n - 1)),
+        _ => Err(format!("{} has {} games, there is no game {}", path, games.len(), number)),
+    }
+}
+
+// who is playing and how far into the game the board is, the result takes over the title at the end
+fn show_replay(game_window: &mut GlutinWindow, b: &Board, game: &PgnGame, status: GameStatus) {
+    if status != GameStatus::Ongoing {
+        return;
+    }
+    game_window.set_title(format!(
+        "chess - {} vs {}, move {} of {}",
+        game.tag("White").unwrap_or("?"),
+        game.tag("Black").unwrap_or("?"),
+        b.get_move_history().len(),
+        game.moves.len()
+    ));
+}
+
+fn save_pgn(path: &str, b: &Board, tags: &Tags, status: GameStatus) {
+    match std::fs::write(path, b.to_pgn(tags, status)) {
+        Ok(()) => println!("saved the game to {}", path),
+        Err(e) => eprintln!("could not save the game to {}: {}", path, e),
+    }
+}
+
+// the engine's current depth, score and first few moves of its main line
+fn analysis_title(info: &chess::uci_client::Info) -> String {
+    let score = match info.score {
+        Some(Score::Centipawns(cp)) => format!("{:+.2}", cp as f64 / 100.0),
+        Some(Score::Mate(moves)) => format!("mate {}", moves),
+        None => "?".to_string(),
+    };
+    let pv: Vec<&str> = info.pv.iter().take(6).map(|m| m.as_str()).collect();
+    format!("chess - depth {} {} {}", info.depth.unwrap_or(0), score, pv.join(" "))
+}
+
+// puts the result in the window title once the game is over and clears it again if moves get taken back,
+// while the game is going it points out when a draw can be claimed
+fn show_status(game_window: &mut GlutinWindow, b: &Board, status: GameStatus) {
+    match (status, b.claimable_draw()) {
+        (GameStatus::Ongoing, Some(reason)) => game_window.set_title(format!("chess - press d to claim a draw by {}", reason)),
+        (GameStatus::Ongoing, None) => game_window.set_title("chess".to_string()),
+        _ => game_window.set_title(format!("chess - {}", status)),
+    }
 }
@@ -39,7 +39,7 @@ fn solve_n_queens(n: i32) -> Vec<Vec<String>> {
 }
 
 // puts each of the n queen boards into fen strings instead
-// did not fix case for n > 10 since fen string will do ...10Q5/... will be parsed as move 1 and 0 instead of move 10 
+// runs of 10 or more empty squares are written as one number like ...10Q5/... which the fen parser reads back as ten
 pub fn n_queens_fen(n:i32) -> Vec<String> {
     let queens = solve_n_queens(n);
     let mut ans = vec![];
@@ -69,6 +69,8 @@ pub fn n_queens_fen(n:i32) -> Vec<String> {
 
             fen += "/";
         }
+        // the last rank does not get a separator after it
+        fen.pop();
         ans.push(fen);
     }
     ans
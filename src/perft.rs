@@ -0,0 +1,166 @@
+// perft counts every leaf of the legal move tree to a fixed depth so the move generator can be checked
+// against the published numbers for well known positions
+use crate::board::*;
+
+impl Board {
+    pub fn perft(&self, depth: u32) -> u64 {
+        let mut b = self.clone();
+        b.count_leaves(depth)
+    }
+
+    // the perft count under each legal move, useful for tracking down which move a wrong total comes from
+    pub fn divide(&self, depth: u32) -> Vec<(Move, u64)> {
+        let mut b = self.clone();
+        let mut ans = vec![];
+        if depth == 0 {
+            return ans;
+        }
+
+        for m in b.legal_moves() {
+            b.make_move(m);
+            ans.push((m, b.count_leaves(depth - 1)));
+            b.unmake_move();
+        }
+        ans
+    }
+
+    fn count_leaves(&mut self, depth: u32) -> u64 {
+        if depth == 0 {
+            return 1;
+        }
+
+        let moves = self.legal_moves();
+        // the last ply does not need to be played out since every legal move is exactly one leaf
+        if depth == 1 {
+            return moves.len() as u64;
+        }
+
+        let mut nodes = 0;
+        for m in moves {
+            self.make_move(m);
+            nodes += self.count_leaves(depth - 1);
+            self.unmake_move();
+        }
+        nodes
+    }
+}
+
+// runs `chess perft <fen> <depth> [board size]` and prints the divide output along with the total
+pub fn run(args: &[String]) -> Result<(), String> {
+    if args.len() < 2 {
+        return Err("usage: chess perft <fen> <depth> [board size]".to_string());
+    }
+
+    let depth = args[1].parse::<u32>().map_err(|_| format!("invalid depth \"{}\"", args[1]))?;
+    let size = match args.get(2) {
+        Some(v) => v.parse::<i32>().map_err(|_| format!("invalid board size \"{}\"", v))?,
+        None => 8,
+    };
+    let board = Board::from_fen(&args[0], size).map_err(|e| format!("could not load fen \"{}\": {}", args[0], e))?;
+
+    let start = std::time::Instant::now();
+    let total = match depth {
+        // divide has no moves to split the count by at depth 0 so the position itself is the only leaf
+        0 => board.perft(0),
+        _ => board
+            .divide(depth)
+            .into_iter()
+            .map(|(m, nodes)| {
+                println!("{}: {}", board.move_to_string(m), nodes);
+                nodes
+            })
+            .sum(),
+    };
+
+    let elapsed = start.elapsed();
+    println!();
+    println!("nodes: {}", total);
+    println!("time: {:.3}s ({:.0} nodes/s)", elapsed.as_secs_f64(), total as f64 / elapsed.as_secs_f64().max(1e-9));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const START: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+    const KIWIPETE: &str = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+    const POSITION_3: &str = "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1";
+    const POSITION_4: &str = "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1";
+    const POSITION_4_MIRRORED: &str = "r2q1rk1/pP1p2pp/Q4n2/bbp1p3/Np6/1B3NBn/pPPP1PPP/R3K2R b KQ - 0 1";
+    const POSITION_5: &str = "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8";
+    const POSITION_6: &str = "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10";
+
+    fn check(fen: &str, expected: &[u64]) {
+        let b = Board::from_fen(fen, 8).unwrap();
+        for (depth, nodes) in expected.iter().enumerate() {
+            assert_eq!(b.perft(depth as u32 + 1), *nodes, "{} at depth {}", fen, depth + 1);
+        }
+    }
+
+    #[test]
+    fn start_position() {
+        check(START, &[20, 400, 8902]);
+    }
+
+    #[test]
+    #[ignore]
+    fn start_position_deep() {
+        check(START, &[20, 400, 8902, 197281, 4865609]);
+    }
+
+    #[test]
+    fn kiwipete() {
+        check(KIWIPETE, &[48, 2039, 97862]);
+    }
+
+    #[test]
+    #[ignore]
+    fn kiwipete_deep() {
+        check(KIWIPETE, &[48, 2039, 97862, 4085603]);
+    }
+
+    #[test]
+    fn position_3() {
+        check(POSITION_3, &[14, 191, 2812, 43238]);
+    }
+
+    #[test]
+    fn position_4() {
+        check(POSITION_4, &[6, 264, 9467]);
+        check(POSITION_4_MIRRORED, &[6, 264, 9467]);
+    }
+
+    #[test]
+    fn position_5() {
+        check(POSITION_5, &[44, 1486, 62379]);
+    }
+
+    #[test]
+    fn position_6() {
+        check(POSITION_6, &[46, 2079, 89890]);
+    }
+
+    #[test]
+    fn mailbox_matches_bitboards() {
+        for fen in [KIWIPETE, POSITION_3, POSITION_4, POSITION_5] {
+            let b = Board::from_fen(fen, 8).unwrap();
+            assert_eq!(b.mailbox_only().perft(2), b.perft(2), "{}", fen);
+        }
+    }
+
+    #[test]
+    fn divide_adds_up_to_perft() {
+        let b = Board::from_fen(KIWIPETE, 8).unwrap();
+        let divide = b.divide(2);
+        assert_eq!(divide.len(), 48);
+        assert_eq!(divide.iter().map(|(_, n)| n).sum::<u64>(), b.perft(2));
+    }
+
+    #[test]
+    fn perft_leaves_the_board_untouched() {
+        let b = Board::from_fen(POSITION_5, 8).unwrap();
+        b.perft(2);
+        assert_eq!(b.to_fen(), POSITION_5);
+    }
+}
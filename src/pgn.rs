@@ -0,0 +1,653 @@
+// portable game notation, the usual way to save a game so other programs can read it back
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::board::*;
+
+// export format keeps movetext lines under 80 characters
+const LINE_LENGTH: usize = 79;
+
+// the seven tag roster, every pgn game has these in this order
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tags {
+    pub event: String,
+    pub site: String,
+    pub date: String,
+    pub round: String,
+    pub white: String,
+    pub black: String,
+}
+
+impl Tags {
+    // a casual game played today, the unknown tags get the standard ?
+    pub fn new(white: &str, black: &str) -> Self {
+        Tags {
+            event: "Casual game".to_string(),
+            site: "?".to_string(),
+            date: today(),
+            round: "?".to_string(),
+            white: white.to_string(),
+            black: black.to_string(),
+        }
+    }
+}
+
+pub fn result_string(status: GameStatus) -> &'static str {
+    match status {
+        GameStatus::Checkmate(Color::Light) => "1-0",
+        GameStatus::Checkmate(Color::Dark) => "0-1",
+        GameStatus::Stalemate | GameStatus::Draw(_) => "1/2-1/2",
+        GameStatus::Ongoing => "*",
+    }
+}
+
+impl Board {
+    // standard algebraic notation like Nbd7, exd6, O-O or e8=Q#, m has to be legal here
+    pub fn move_to_san(&self, m: Move) -> String {
+        let piece = self.piece_at(m.from).unwrap().get_piecetype();
+        let mut san = String::new();
+
+        if m.has(Move::CASTLE) {
+            san += match m.to > m.from {
+                true => "O-O",
+                false => "O-O-O",
+            };
+        }
+        else {
+            let from = self.square_to_algebraic(m.from);
+            let (file, rank) = from.split_at(1);
+            if piece == PieceType::Pawn {
+                if m.has(Move::CAPTURE) {
+                    san += file;
+                }
+            }
+            else {
+                san.push(Piece::from_type(piece, Color::Light).get_code());
+
+                // other pieces of the same kind that could also go to the same square
+                let size = self.get_size() as usize;
+                let others: Vec<usize> = self
+                    .legal_moves()
+                    .into_iter()
+                    .filter(|o| o.to == m.to && o.from != m.from)
+                    .filter(|o| self.piece_at(o.from).unwrap().get_piecetype() == piece)
+                    .map(|o| o.from)
+                    .collect();
+                if !others.is_empty() {
+                    if others.iter().all(|o| o % size != m.from % size) {
+                        san += file;
+                    }
+                    else if others.iter().all(|o| o / size != m.from / size) {
+                        san += rank;
+                    }
+                    else {
+                        san += &from;
+                    }
+                }
+            }
+            if m.has(Move::CAPTURE) {
+                san.push('x');
+            }
+            san += &self.square_to_algebraic(m.to);
+            if let Some(p) = m.promotion {
+                san.push('=');
+                san.push(Piece::from_type(p, Color::Light).get_code());
+            }
+        }
+
+        let mut after = self.clone();
+        after.make_move(m);
+        if after.in_check(after.get_turn()) {
+            san.push(match after.legal_moves().is_empty() {
+                true => '#',
+                false => '+',
+            });
+        }
+        san
+    }
+
+    // reads a move in standard algebraic notation, being lenient about check marks, annotations, 0-0 for castling,
+    // an e.p. after an en passant capture, a P for pawn moves, a missing = before the promotion piece and more
+    // disambiguation than needed
+    pub fn move_from_san(&self, san: &str) -> Option<Move> {
+        let san = san.trim_end_matches(['+', '#', '!', '?']);
+        let san = san.strip_suffix("e.p.").unwrap_or(san).trim_end();
+        let moves = self.legal_moves();
+        let castle = san.replace('0', "O");
+        if castle == "O-O" || castle == "O-O-O" {
+            let king_side = castle == "O-O";
+            return moves.into_iter().find(|m| m.has(Move::CASTLE) && (m.to > m.from) == king_side);
+        }
+
+        let (piece, rest) = match san.chars().next()? {
+            'N' => (PieceType::Knight, &san[1..]),
+            'B' => (PieceType::Bishop, &san[1..]),
+            'R' => (PieceType::Rook, &san[1..]),
+            'Q' => (PieceType::Queen, &san[1..]),
+            'K' => (PieceType::King, &san[1..]),
+            'P' => (PieceType::Pawn, &san[1..]),
+            _ => (PieceType::Pawn, san),
+        };
+        let rest: String = rest.chars().filter(|c| *c != 'x' && *c != '-' && *c != '=').collect();
+
+        // a square always ends in a digit so a letter at the end is the promotion piece
+        let promotion = match rest.chars().last()? {
+            c if c.is_ascii_digit() => None,
+            c => Some(match c.to_ascii_uppercase() {
+                'N' => PieceType::Knight,
+                'B' => PieceType::Bishop,
+                'R' => PieceType::Rook,
+                'Q' => PieceType::Queen,
+                _ => return None,
+            }),
+        };
+        let rest = match promotion {
+            Some(_) => &rest[..rest.len() - 1],
+            None => &rest[..],
+        };
+
+        // the target square starts at the last file letter and anything before it says which piece is moving
+        let split = rest.rfind(|c: char| c.is_ascii_lowercase())?;
+        let to = self.square_from_algebraic(&rest[split..])?;
+        let hint = &rest[..split];
+        let hint_file = hint.chars().next().filter(|c| c.is_ascii_lowercase());
+        let hint_rank = hint.trim_start_matches(|c: char| c.is_ascii_lowercase());
+
+        let mut found = moves.into_iter().filter(|m| {
+            let from = self.square_to_algebraic(m.from);
+            m.to == to
+                && m.promotion == promotion
+                && self.piece_at(m.from).map(|p| p.get_piecetype()) == Some(piece)
+                && hint_file.is_none_or(|f| from.starts_with(f))
+                && (hint_rank.is_empty() || from[1..] == *hint_rank)
+        });
+        match (found.next(), found.next()) {
+            (Some(m), None) => Some(m),
+            _ => None,
+        }
+    }
+
+    // the whole game from the position the board started in, with FEN and SetUp tags when that isn't the
+    // standard start
+    pub fn to_pgn(&self, tags: &Tags, status: GameStatus) -> String {
+        let mut start = self.clone();
+        while start.unmake_move() {}
+        let start_fen = start.to_fen();
+        let result = result_string(status);
+
+        let mut pgn = String::new();
+        for (name, value) in [
+            ("Event", &tags.event),
+            ("Site", &tags.site),
+            ("Date", &tags.date),
+            ("Round", &tags.round),
+            ("White", &tags.white),
+            ("Black", &tags.black),
+        ] {
+            pgn += &format!("[{} \"{}\"]\n", name, escape(value));
+        }
+        pgn += &format!("[Result \"{}\"]\n", result);
+        if start_fen != START_FEN {
+            pgn += "[SetUp \"1\"]\n";
+            pgn += &format!("[FEN \"{}\"]\n", start_fen);
+        }
+        pgn.push('\n');
+
+        // the movetext is built up as words and then wrapped
+        let mut words = vec![];
+        let mut b = start;
+        for (i, m) in self.get_move_history().into_iter().enumerate() {
+            match b.get_turn() {
+                Color::Light => words.push(format!("{}.", b.get_fullmove_number())),
+                Color::Dark if i == 0 => words.push(format!("{}...", b.get_fullmove_number())),
+                Color::Dark => (),
+            }
+            words.push(b.move_to_san(m));
+            b.make_move(m);
+        }
+        words.push(result.to_string());
+
+        let mut line = String::new();
+        for word in words {
+            if !line.is_empty() && line.len() + 1 + word.len() > LINE_LENGTH {
+                pgn += &line;
+                pgn.push('\n');
+                line.clear();
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line += &word;
+        }
+        pgn += &line;
+        pgn.push('\n');
+        pgn
+    }
+}
+
+// one game read from a pgn file, only the main line is kept
+#[derive(Debug, Clone, PartialEq)]
+pub struct PgnGame {
+    // every tag in the order it appeared
+    pub tags: Vec<(String, String)>,
+    pub start_fen: String,
+    pub moves: Vec<Move>,
+    pub result: String,
+}
+
+impl PgnGame {
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags.iter().find(|(n, _)| n == name).map(|(_, v)| v.as_str())
+    }
+
+    // the board the game starts from, play moves on it in order to replay the game
+    pub fn start_board(&self) -> Board {
+        Board::from_fen(&self.start_fen, fen_size(&self.start_fen)).unwrap()
+    }
+}
+
+// everything that can be wrong with a pgn file, lines count from 1
+#[derive(Debug, Clone, PartialEq)]
+pub enum PgnError {
+    BadTag { line: usize },
+    UnterminatedComment { line: usize },
+    UnbalancedVariation { line: usize },
+    BadFen { line: usize, error: FenError },
+    IllegalMove { line: usize, san: String },
+}
+
+impl std::fmt::Display for PgnError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            PgnError::BadTag { line } => write!(f, "badly formed tag on line {}", line),
+            PgnError::UnterminatedComment { line } => write!(f, "comment starting on line {} is never closed", line),
+            PgnError::UnbalancedVariation { line } => write!(f, "unmatched parenthesis on line {}", line),
+            PgnError::BadFen { line, error } => write!(f, "bad FEN tag on line {}: {}", line, error),
+            PgnError::IllegalMove { line, san } => write!(f, "illegal move {} on line {}", san, line),
+        }
+    }
+}
+
+impl std::error::Error for PgnError {}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Tag(String, String),
+    // a move in standard algebraic notation
+    San(String),
+    Result(String),
+    VariationStart,
+    VariationEnd,
+}
+
+// the fen is for a board with as many ranks as it has
+fn fen_size(fen: &str) -> i32 {
+    fen.split_whitespace().next().unwrap_or("").split('/').count() as i32
+}
+
+// splits the file into tokens with their line numbers, comments, nags and move numbers are dropped here
+fn tokenize(text: &str) -> Result<Vec<(Token, usize)>, PgnError> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut tokens = vec![];
+    let mut line = 1;
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let at_line_start = i == 0 || chars[i - 1] == '\n';
+        match c {
+            '\n' => line += 1,
+            // an escaped line for other programs
+            '%' if at_line_start => {
+                while i < chars.len() && chars[i] != '\n' {
+                    i += 1;
+                }
+                continue;
+            }
+            ';' => {
+                while i < chars.len() && chars[i] != '\n' {
+                    i += 1;
+                }
+                continue;
+            }
+            '{' => {
+                let start = line;
+                while i < chars.len() && chars[i] != '}' {
+                    if chars[i] == '\n' {
+                        line += 1;
+                    }
+                    i += 1;
+                }
+                if i == chars.len() {
+                    return Err(PgnError::UnterminatedComment { line: start });
+                }
+            }
+            '[' => {
+                // a ] inside the quoted value doesn't end the tag
+                let mut end = i + 1;
+                let mut quoted = false;
+                while end < chars.len() && chars[end] != '\n' && (quoted || chars[end] != ']') {
+                    match chars[end] {
+                        '"' => quoted = !quoted,
+                        '\\' => end += 1,
+                        _ => (),
+                    }
+                    end += 1;
+                }
+                if end >= chars.len() || chars[end] != ']' {
+                    return Err(PgnError::BadTag { line });
+                }
+                let tag: String = chars[i + 1..end].iter().collect();
+                tokens.push((parse_tag(&tag).ok_or(PgnError::BadTag { line })?, line));
+                i = end;
+            }
+            '(' => tokens.push((Token::VariationStart, line)),
+            ')' => tokens.push((Token::VariationEnd, line)),
+            '$' => {
+                while i + 1 < chars.len() && chars[i + 1].is_ascii_digit() {
+                    i += 1;
+                }
+            }
+            c if c.is_alphanumeric() || c == '*' => {
+                let start = i;
+                while i + 1 < chars.len() && (chars[i + 1].is_alphanumeric() || "_+#=:-/!?*".contains(chars[i + 1])) {
+                    i += 1;
+                }
+                let mut word: String = chars[start..=i].iter().collect();
+                // the dots in an e.p. after an en passant capture split it off into words of its own
+                if word.ends_with('e') && chars[i + 1..].starts_with(&['.', 'p', '.']) {
+                    word.pop();
+                    i += 3;
+                }
+                match word.as_str() {
+                    "" => (),
+                    "1-0" | "0-1" | "1/2-1/2" | "*" => tokens.push((Token::Result(word), line)),
+                    // move numbers, the dots after them are skipped as separators
+                    _ if word.chars().all(|c| c.is_ascii_digit()) => (),
+                    _ => tokens.push((Token::San(word), line)),
+                }
+            }
+            // spaces, the dots after move numbers and annotation glyphs like !? standing on their own
+            _ => (),
+        }
+        i += 1;
+    }
+    Ok(tokens)
+}
+
+// the inside of a tag pair like Event "Some \"quoted\" name"
+fn parse_tag(tag: &str) -> Option<Token> {
+    let (name, value) = tag.trim().split_once(char::is_whitespace)?;
+    let value = value.trim().strip_prefix('"')?.strip_suffix('"')?;
+    let mut unescaped = String::new();
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => unescaped.push(chars.next()?),
+            c => unescaped.push(c),
+        }
+    }
+    Some(Token::Tag(name.to_string(), unescaped))
+}
+
+// every game in the file, each one's main line checked move by move on a board
+pub fn parse_pgn(text: &str) -> Result<Vec<PgnGame>, PgnError> {
+    let mut games = vec![];
+    let mut tags: Vec<(String, String)> = vec![];
+    let mut board: Option<Board> = None;
+    let mut moves = vec![];
+    // how many variations deep the parser is, moves inside variations are skipped
+    let mut depth = 0;
+
+    // the line of the last token, where a game left open at the end of the file finishes
+    let mut last_line = 1;
+
+    let mut finish = |tags: &mut Vec<(String, String)>, mut b: Board, moves: &mut Vec<Move>, result: String| {
+        while b.unmake_move() {}
+        games.push(PgnGame {
+            tags: std::mem::take(tags),
+            start_fen: b.to_fen(),
+            moves: std::mem::take(moves),
+            result,
+        });
+    };
+
+    for (token, line) in tokenize(text)? {
+        last_line = line;
+        match token {
+            Token::Tag(name, value) => {
+                // tags after some movetext start the next game even when the last one had no result
+                if let Some(b) = board.take() {
+                    finish(&mut tags, b, &mut moves, "*".to_string());
+                }
+                tags.push((name, value));
+            }
+            Token::VariationStart => depth += 1,
+            Token::VariationEnd if depth == 0 => return Err(PgnError::UnbalancedVariation { line }),
+            Token::VariationEnd => depth -= 1,
+            Token::San(_) if depth > 0 => (),
+            Token::San(san) => {
+                if board.is_none() {
+                    board = Some(start_position(&tags, line)?);
+                }
+                let b = board.as_mut().unwrap();
+                match b.move_from_san(&san) {
+                    Some(m) => {
+                        b.make_move(m);
+                        moves.push(m);
+                    }
+                    None => return Err(PgnError::IllegalMove { line, san }),
+                }
+            }
+            Token::Result(_) if depth > 0 => (),
+            Token::Result(result) => {
+                let b = match board.take() {
+                    Some(b) => b,
+                    None => start_position(&tags, line)?,
+                };
+                finish(&mut tags, b, &mut moves, result);
+            }
+        }
+    }
+    if depth > 0 {
+        return Err(PgnError::UnbalancedVariation { line: text.lines().count() });
+    }
+    // a game with only tags still starts from its FEN
+    let board = match board {
+        None if !tags.is_empty() => Some(start_position(&tags, last_line)?),
+        board => board,
+    };
+    if let Some(b) = board {
+        finish(&mut tags, b, &mut moves, "*".to_string());
+    }
+    Ok(games)
+}
+
+// the standard start unless the tags give a FEN
+fn start_position(tags: &[(String, String)], line: usize) -> Result<Board, PgnError> {
+    let fen = tags.iter().find(|(name, _)| name == "FEN").map_or(START_FEN, |(_, fen)| fen.as_str());
+    Board::from_fen(fen, fen_size(fen)).map_err(|error| PgnError::BadFen { line, error })
+}
+
+// quotes and backslashes inside a tag value have to be escaped
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+// the date as YYYY.MM.DD in utc, worked out from the days since 1970 by the usual civil calendar conversion
+fn today() -> String {
+    let days = match SystemTime::now().duration_since(UNIX_EPOCH) {
+        Ok(d) => (d.as_secs() / 86400) as i64,
+        Err(_) => return "????.??.??".to_string(),
+    };
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z.rem_euclid(146097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{:04}.{:02}.{:02}", year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn san(fen: &str, mv: &str) -> String {
+        let b = Board::from_fen(fen, 8).unwrap();
+        b.move_to_san(b.move_from_string(mv).unwrap())
+    }
+
+    fn play(fen: &str, moves: &[&str]) -> Board {
+        let mut b = Board::from_fen(fen, 8).unwrap();
+        for mv in moves {
+            let m = b.move_from_string(mv).unwrap();
+            b.make_move(m);
+        }
+        b
+    }
+
+    fn tags() -> Tags {
+        Tags {
+            date: "2024.01.02".to_string(),
+            ..Tags::new("Light", "Dark")
+        }
+    }
+
+    #[test]
+    fn standard_algebraic_notation() {
+        assert_eq!(san(START_FEN, "g1f3"), "Nf3");
+        assert_eq!(san(START_FEN, "e2e4"), "e4");
+        // both knights can reach d2 so the file is given, both rooks on the first rank need the file too
+        assert_eq!(san("4k3/8/8/8/8/8/8/RN2KN1R w - - 0 1", "b1d2"), "Nbd2");
+        assert_eq!(san("4k3/8/8/8/8/8/4K3/R6R w - - 0 1", "a1d1"), "Rad1");
+        // rooks on the same file are told apart by rank
+        assert_eq!(san("4k3/8/R7/8/8/8/8/R3K3 w - - 0 1", "a1a3"), "R1a3");
+        assert_eq!(san("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1", "e1g1"), "O-O");
+        assert_eq!(san("r3k2r/8/8/8/8/8/8/R3K2R b KQkq - 0 1", "e8c8"), "O-O-O");
+        assert_eq!(san("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1", "e5d6"), "exd6");
+        assert_eq!(san("3k4/6P1/8/8/8/8/8/4K3 w - - 0 1", "g7g8q"), "g8=Q+");
+        assert_eq!(san("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1", "a1a8"), "Ra8#");
+    }
+
+    #[test]
+    fn exports_a_finished_game() {
+        let b = play(START_FEN, &["e2e4", "e7e5", "f1c4", "b8c6", "d1h5", "g8f6", "h5f7"]);
+        let status = b.game_status();
+        assert_eq!(
+            b.to_pgn(&tags(), status),
+            "[Event \"Casual game\"]\n\
+             [Site \"?\"]\n\
+             [Date \"2024.01.02\"]\n\
+             [Round \"?\"]\n\
+             [White \"Light\"]\n\
+             [Black \"Dark\"]\n\
+             [Result \"1-0\"]\n\
+             \n\
+             1. e4 e5 2. Bc4 Nc6 3. Qh5 Nf6 4. Qxf7# 1-0\n"
+        );
+    }
+
+    #[test]
+    fn custom_start_gets_fen_tags() {
+        let fen = "4k3/8/8/8/8/8/4P3/4K3 b - - 0 12";
+        let b = play(fen, &["e8d7", "e2e4"]);
+        let pgn = b.to_pgn(&tags(), GameStatus::Ongoing);
+        assert!(pgn.contains("[SetUp \"1\"]\n[FEN \"4k3/8/8/8/8/8/4P3/4K3 b - - 0 12\"]\n"));
+        assert!(pgn.ends_with("\n12... Kd7 13. e4 *\n"));
+    }
+
+    #[test]
+    fn reads_lenient_san() {
+        let b = Board::from_fen(START_FEN, 8).unwrap();
+        assert_eq!(b.move_from_san("Ngf3").map(|m| b.move_to_string(m)), Some("g1f3".to_string()));
+        assert_eq!(b.move_from_san("e4!?").map(|m| b.move_to_string(m)), Some("e2e4".to_string()));
+        assert_eq!(b.move_from_san("Nd2"), None);
+
+        let b = Board::from_fen("r3k2r/1P6/8/8/8/8/8/R3K2R w KQkq - 0 1", 8).unwrap();
+        assert_eq!(b.move_from_san("0-0").map(|m| b.move_to_string(m)), Some("e1g1".to_string()));
+        assert_eq!(b.move_from_san("O-O-O").map(|m| b.move_to_string(m)), Some("e1c1".to_string()));
+        assert_eq!(b.move_from_san("bxa8Q+").map(|m| b.move_to_string(m)), Some("b7a8q".to_string()));
+        assert_eq!(b.move_from_san("b8=N").map(|m| b.move_to_string(m)), Some("b7b8n".to_string()));
+
+        let b = Board::from_fen("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1", 8).unwrap();
+        assert_eq!(b.move_from_san("exd6 e.p.").map(|m| b.move_to_string(m)), Some("e5d6".to_string()));
+        assert_eq!(b.move_from_san("Pe6").map(|m| b.move_to_string(m)), Some("e5e6".to_string()));
+        assert_eq!(b.move_from_san("exd6e.p.").map(|m| b.move_to_string(m)), Some("e5d6".to_string()));
+        let games = parse_pgn("[FEN \"4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1\"]\n\n1. exd6 e.p. Kf7 2. Pd7 *").unwrap();
+        assert_eq!(games[0].moves.len(), 3);
+    }
+
+    #[test]
+    fn parses_games_with_comments_and_variations() {
+        let text = r#"[Event "First \"test\" game"]
+[Site "?"]
+[Result "1-0"]
+
+% an escaped line
+1. e4 {best by test} e5 $1 2. Nf3 (2. f4 exf4 (2... d5) 3. Nf3) 2... Nc6 ; a rest of line comment
+3. Bb5 a6!? 1-0
+
+[Event "Second"]
+[SetUp "1"]
+[FEN "4k3/8/8/8/8/8/4P3/4K3 b - - 0 12"]
+
+12... Kd7 13. e4 *
+"#;
+        let games = parse_pgn(text).unwrap();
+        assert_eq!(games.len(), 2);
+
+        assert_eq!(games[0].tag("Event"), Some("First \"test\" game"));
+        assert_eq!(games[0].result, "1-0");
+        let mut b = games[0].start_board();
+        for m in &games[0].moves {
+            b.make_move(*m);
+        }
+        assert_eq!(b.to_fen(), "r1bqkbnr/1ppp1ppp/p1n5/1B2p3/4P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 0 4");
+
+        assert_eq!(games[1].start_fen, "4k3/8/8/8/8/8/4P3/4K3 b - - 0 12");
+        assert_eq!(games[1].moves.len(), 2);
+        assert_eq!(games[1].result, "*");
+
+        // no movetext and no result at all
+        let games = parse_pgn("[Event \"Setup\"]\n[FEN \"4k3/8/8/8/8/8/8/4K2R w K - 0 1\"]\n").unwrap();
+        assert_eq!(games.len(), 1);
+        assert_eq!(games[0].start_fen, "4k3/8/8/8/8/8/8/4K2R w K - 0 1");
+        assert!(games[0].moves.is_empty());
+    }
+
+    #[test]
+    fn exported_games_read_back() {
+        let b = play("4k3/8/8/8/8/8/4P3/4K3 b - - 0 12", &["e8d7", "e2e4", "d7d6", "e4e5", "d6e5"]);
+        let games = parse_pgn(&b.to_pgn(&tags(), GameStatus::Draw(DrawReason::InsufficientMaterial))).unwrap();
+        assert_eq!(games.len(), 1);
+        assert_eq!(games[0].moves, b.get_move_history());
+        assert_eq!(games[0].result, "1/2-1/2");
+        assert_eq!(games[0].tag("White"), Some("Light"));
+    }
+
+    #[test]
+    fn reports_where_the_file_is_wrong() {
+        assert_eq!(
+            parse_pgn("[Event \"x\"]\n\n1. e4 e5 2. Ke3 *"),
+            Err(PgnError::IllegalMove { line: 3, san: "Ke3".to_string() })
+        );
+        assert_eq!(parse_pgn("1. e4 (1. d4 d5 *"), Err(PgnError::UnbalancedVariation { line: 1 }));
+        assert_eq!(parse_pgn("1. e4 {never closed\n*"), Err(PgnError::UnterminatedComment { line: 1 }));
+        assert_eq!(parse_pgn("[Event x]\n*"), Err(PgnError::BadTag { line: 1 }));
+    }
+
+    #[test]
+    fn long_games_are_wrapped() {
+        let mut moves = vec![];
+        for _ in 0..10 {
+            moves.extend(["g1f3", "g8f6", "f3g1", "f6g8"]);
+        }
+        let b = play(START_FEN, &moves);
+        let pgn = b.to_pgn(&tags(), GameStatus::Ongoing);
+        let movetext = pgn.split("\n\n").nth(1).unwrap();
+        assert!(movetext.lines().count() > 1);
+        assert!(movetext.lines().all(|l| l.len() <= LINE_LENGTH));
+    }
+}
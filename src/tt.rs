@@ -0,0 +1,114 @@
+// a fixed size table of search results keyed by zobrist hash so positions reached by different move orders
+// only get searched once
+use std::mem::size_of;
+
+use crate::board::*;
+use crate::engine::MATE_BOUND;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Bound {
+    // the score is exact
+    Exact,
+    // the search failed high so the real score is at least this
+    Lower,
+    // no move raised alpha so the real score is at most this
+    Upper,
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct Entry {
+    pub hash: u64,
+    pub depth: u32,
+    pub bound: Bound,
+    // mates are stored as the distance from this position rather than from the root, see probe and store
+    pub score: i32,
+    pub best_move: Option<Move>,
+}
+
+pub struct TranspositionTable {
+    entries: Vec<Option<Entry>>,
+}
+
+impl TranspositionTable {
+    pub fn new(megabytes: usize) -> Self {
+        let len = (megabytes * 1024 * 1024 / size_of::<Option<Entry>>()).max(1);
+        TranspositionTable { entries: vec![None; len] }
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.fill(None);
+    }
+
+    fn index(&self, hash: u64) -> usize {
+        (hash % self.entries.len() as u64) as usize
+    }
+
+    // the entry for hash with its score adjusted to be a mate distance from the root again, ply being how far this
+    // position is from the root
+    pub fn probe(&self, hash: u64, ply: i32) -> Option<Entry> {
+        match self.entries[self.index(hash)] {
+            Some(mut entry) if entry.hash == hash => {
+                if entry.score >= MATE_BOUND {
+                    entry.score -= ply;
+                }
+                else if entry.score <= -MATE_BOUND {
+                    entry.score += ply;
+                }
+                Some(entry)
+            }
+            _ => None,
+        }
+    }
+
+    // another position's entry is always replaced but for the same position a shallower search doesn't overwrite
+    // a deeper one
+    pub fn store(&mut self, hash: u64, depth: u32, bound: Bound, mut score: i32, best_move: Option<Move>, ply: i32) {
+        let index = self.index(hash);
+        if let Some(old) = self.entries[index] {
+            if old.hash == hash && old.depth > depth {
+                return;
+            }
+        }
+
+        if score >= MATE_BOUND {
+            score += ply;
+        }
+        else if score <= -MATE_BOUND {
+            score -= ply;
+        }
+        self.entries[index] = Some(Entry { hash, depth, bound, score, best_move });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::MATE;
+
+    #[test]
+    fn entries_come_back_for_the_same_hash_only() {
+        let mut tt = TranspositionTable::new(1);
+        let m = Move { from: 52, to: 36, flags: Move::DOUBLE_PUSH, promotion: None };
+        tt.store(12345, 3, Bound::Exact, 42, Some(m), 0);
+
+        let entry = tt.probe(12345, 0).unwrap();
+        assert_eq!((entry.depth, entry.bound, entry.score, entry.best_move), (3, Bound::Exact, 42, Some(m)));
+        assert!(tt.probe(12346, 0).is_none());
+
+        // a shallower result for the same position keeps the deeper one
+        tt.store(12345, 1, Bound::Lower, 7, None, 0);
+        assert_eq!(tt.probe(12345, 0).unwrap().score, 42);
+    }
+
+    #[test]
+    fn mate_scores_are_relative_to_the_position() {
+        let mut tt = TranspositionTable::new(1);
+        // mate in 3 plies from a position found 5 plies into the search
+        tt.store(99, 4, Bound::Exact, MATE - 8, None, 5);
+        assert_eq!(tt.probe(99, 0).unwrap().score, MATE - 3);
+        assert_eq!(tt.probe(99, 2).unwrap().score, MATE - 5);
+
+        tt.store(100, 4, Bound::Exact, -MATE + 6, None, 2);
+        assert_eq!(tt.probe(100, 7).unwrap().score, -MATE + 11);
+    }
+}
@@ -0,0 +1,251 @@
+// the universal chess interface, guis and tournament managers send commands on stdin and read the replies on stdout
+// the search runs on its own thread so stop and isready still get answered while it is thinking
+use std::io::BufRead;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::board::*;
+use crate::engine::{
+    time_for_move, Engine, SearchLimits, SearchResult, DEFAULT_HASH_MB, DEFAULT_MOVES_TO_GO, MATE, MATE_BOUND, MAX_DEPTH,
+};
+
+const MAX_HASH_MB: usize = 1024;
+
+struct Uci {
+    board: Board,
+    engine: Arc<Mutex<Engine>>,
+    stop: Arc<AtomicBool>,
+    search: Option<JoinHandle<()>>,
+}
+
+// handles each line from stdin in turn, a running search is stopped before returning
+pub fn run() {
+    let mut uci = Uci::new();
+    for line in std::io::stdin().lock().lines() {
+        match line {
+            Ok(line) if uci.handle(&line) => (),
+            _ => break,
+        }
+    }
+    uci.stop_search();
+}
+
+impl Uci {
+    fn new() -> Self {
+        let engine = Engine::new(DEFAULT_HASH_MB);
+        Uci {
+            board: Board::from_fen(START_FEN, 8).unwrap(),
+            stop: engine.stop_flag(),
+            engine: Arc::new(Mutex::new(engine)),
+            search: None,
+        }
+    }
+
+    // handles one line from the gui and gives back false once it is time to quit
+    fn handle(&mut self, line: &str) -> bool {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let args = words.get(1..).unwrap_or(&[]);
+        match words.first() {
+            Some(&"uci") => {
+                println!("id name chess");
+                println!("option name Hash type spin default {} min 1 max {}", DEFAULT_HASH_MB, MAX_HASH_MB);
+                println!("uciok");
+            }
+            Some(&"isready") => println!("readyok"),
+            Some(&"ucinewgame") => {
+                self.stop_search();
+                self.engine.lock().unwrap().clear();
+                self.board = Board::from_fen(START_FEN, 8).unwrap();
+            }
+            Some(&"position") => {
+                if let Err(e) = self.position(args) {
+                    println!("info string {}", e);
+                }
+            }
+            Some(&"go") => self.go(args),
+            Some(&"stop") => self.stop_search(),
+            Some(&"setoption") => self.set_option(args),
+            Some(&"quit") => return false,
+            // anything else is ignored like the protocol asks
+            _ => (),
+        }
+        true
+    }
+
+    // position startpos or position fen <fen>, either followed by moves and the moves played from there
+    fn position(&mut self, args: &[&str]) -> Result<(), String> {
+        let moves_at = args.iter().position(|a| *a == "moves").unwrap_or(args.len());
+        let board = match args.first() {
+            Some(&"startpos") => Board::from_fen(START_FEN, 8),
+            Some(&"fen") => Board::from_fen(&args[1..moves_at].join(" "), 8),
+            _ => return Err("position needs startpos or fen".to_string()),
+        };
+        let mut board = board.map_err(|e| e.to_string())?;
+
+        // the moves are played rather than jumping to the final position so repetitions are still seen
+        for mv in args.iter().skip(moves_at + 1) {
+            match board.move_from_string(mv) {
+                Some(m) => board.make_move(m),
+                None => return Err(format!("illegal move {} in {}", mv, board.to_fen())),
+            }
+        }
+        self.board = board;
+        Ok(())
+    }
+
+    fn go(&mut self, args: &[&str]) {
+        self.stop_search();
+        let (limits, infinite) = go_limits(args, self.board.get_turn());
+        self.stop.store(false, Ordering::Relaxed);
+
+        let board = self.board.clone();
+        let engine = self.engine.clone();
+        let stop = self.stop.clone();
+        self.search = Some(thread::spawn(move || {
+            let start = Instant::now();
+            let result = engine.lock().unwrap().search_with(&board, limits, |r| {
+                println!("{}", info_line(&board, r, start.elapsed()));
+            });
+
+            // after go infinite the best move can only be sent once the gui says stop, even if the search ran out
+            while infinite && !stop.load(Ordering::Relaxed) {
+                thread::sleep(Duration::from_millis(1));
+            }
+            match result.best_move {
+                Some(m) => println!("bestmove {}", board.move_to_string(m)),
+                None => println!("bestmove 0000"),
+            }
+        }));
+    }
+
+    // ends the running search, which sends its best move, and waits for it to finish
+    fn stop_search(&mut self) {
+        if let Some(search) = self.search.take() {
+            self.stop.store(true, Ordering::Relaxed);
+            search.join().unwrap();
+        }
+    }
+
+    // setoption name <name> value <value>, Hash is the only option there is
+    fn set_option(&mut self, args: &[&str]) {
+        let value_at = args.iter().position(|a| *a == "value").unwrap_or(args.len());
+        let name = args.get(1..value_at).unwrap_or(&[]).join(" ");
+        let value = args.get(value_at + 1..).unwrap_or(&[]).join(" ");
+
+        if !name.eq_ignore_ascii_case("hash") {
+            println!("info string unknown option {}", name);
+            return;
+        }
+        match value.parse::<usize>() {
+            Ok(mb) => {
+                self.stop_search();
+                self.engine.lock().unwrap().set_hash_size(mb.clamp(1, MAX_HASH_MB));
+            }
+            Err(_) => println!("info string bad hash size {}", value),
+        }
+    }
+}
+
+// the search limits for a go command and whether it was go infinite
+fn go_limits(args: &[&str], turn: Color) -> (SearchLimits, bool) {
+    let value = |name: &str| -> Option<u64> {
+        let i = args.iter().position(|a| *a == name)?;
+        args.get(i + 1)?.parse().ok()
+    };
+
+    let infinite = args.contains(&"infinite");
+    let depth = value("depth").map_or(MAX_DEPTH, |d| (d as u32).clamp(1, MAX_DEPTH));
+    let (clock, inc) = match turn {
+        Color::Light => (value("wtime"), value("winc")),
+        Color::Dark => (value("btime"), value("binc")),
+    };
+
+    let time = match (infinite, value("movetime"), clock) {
+        (true, _, _) => None,
+        (false, Some(ms), _) => Some(Duration::from_millis(ms)),
+        (false, None, Some(clock)) => {
+            let moves_to_go = value("movestogo").unwrap_or(DEFAULT_MOVES_TO_GO);
+            Some(time_for_move(clock, inc.unwrap_or(0), moves_to_go))
+        }
+        (false, None, None) => None,
+    };
+
+    let limits = SearchLimits { depth, time };
+    (limits, infinite)
+}
+
+fn info_line(board: &Board, result: &SearchResult, elapsed: Duration) -> String {
+    let ms = elapsed.as_millis() as u64;
+    let mut line = format!(
+        "info depth {} score {} nodes {} nps {} time {}",
+        result.depth,
+        score_string(result.score),
+        result.nodes,
+        result.nodes * 1000 / ms.max(1),
+        ms
+    );
+    if let Some(m) = result.best_move {
+        line += &format!(" pv {}", board.move_to_string(m));
+    }
+    line
+}
+
+// mates are given in moves rather than plies, negative when the engine is the one getting mated
+fn score_string(score: i32) -> String {
+    if score.abs() < MATE_BOUND {
+        return format!("cp {}", score);
+    }
+    // already mated is mate 0 rather than mate -0
+    let moves = (MATE - score.abs() + 1) / 2;
+    match score > 0 || moves == 0 {
+        true => format!("mate {}", moves),
+        false => format!("mate -{}", moves),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn position_plays_the_moves() {
+        let mut uci = Uci::new();
+        uci.handle("position startpos moves e2e4 e7e5 g1f3");
+        assert_eq!(uci.board.to_fen(), "rnbqkbnr/pppp1ppp/8/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 1 2");
+
+        uci.handle("position fen 7k/8/8/8/8/8/P7/K7 w - - 0 1 moves a2a4");
+        assert_eq!(uci.board.to_fen(), "7k/8/8/8/P7/8/8/K7 b - a3 0 1");
+
+        // an illegal move leaves the old position alone
+        uci.handle("position startpos moves e2e5");
+        assert_eq!(uci.board.to_fen(), "7k/8/8/8/P7/8/8/K7 b - a3 0 1");
+    }
+
+    #[test]
+    fn go_limits_from_the_clock() {
+        let (limits, infinite) = go_limits(&["depth", "5"], Color::Light);
+        assert_eq!((limits.depth, limits.time, infinite), (5, None, false));
+
+        let (limits, _) = go_limits(&["movetime", "250"], Color::Dark);
+        assert_eq!(limits.time, Some(Duration::from_millis(250)));
+
+        let (limits, _) = go_limits(&["wtime", "60000", "btime", "3000", "winc", "1000", "binc", "0"], Color::Light);
+        assert_eq!(limits.time, Some(Duration::from_millis(2750)));
+        let (limits, _) = go_limits(&["wtime", "60000", "btime", "3000", "movestogo", "2"], Color::Dark);
+        assert_eq!(limits.time, Some(Duration::from_millis(1500)));
+
+        let (limits, infinite) = go_limits(&["infinite"], Color::Light);
+        assert_eq!((limits.depth, limits.time, infinite), (MAX_DEPTH, None, true));
+    }
+
+    #[test]
+    fn mate_scores_are_in_moves() {
+        assert_eq!(score_string(35), "cp 35");
+        assert_eq!(score_string(MATE - 1), "mate 1");
+        assert_eq!(score_string(MATE - 3), "mate 2");
+        assert_eq!(score_string(-MATE + 2), "mate -1");
+        assert_eq!(score_string(-MATE), "mate 0");
+    }
+}
@@ -0,0 +1,263 @@
+// talks to another engine (like a stockfish binary) over the universal chess interface, the engine's output is read
+// on its own thread and handed over through a channel so the window never waits on it
+use std::io::{self, BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, TryRecvError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::board::*;
+
+// how long the engine gets to answer uci and isready when it starts up
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Score {
+    Centipawns(i32),
+    // moves until mate, negative when the side to move is the one getting mated
+    Mate(i32),
+}
+
+// the parts of an info line that are worth showing, anything the engine leaves out is None or empty
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Info {
+    pub depth: Option<u32>,
+    pub score: Option<Score>,
+    pub nodes: Option<u64>,
+    pub pv: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum EngineMessage {
+    Info(Info),
+    // the move as the engine wrote it like e7e8q, 0000 or (none) when there is no move
+    BestMove(String),
+}
+
+pub struct ExternalEngine {
+    child: Child,
+    stdin: ChildStdin,
+    lines: Receiver<String>,
+    name: String,
+    searching: bool,
+    // searches that were stopped but haven't sent their bestmove yet, everything they say is thrown away
+    stale: u32,
+}
+
+impl ExternalEngine {
+    // starts the engine at path and waits for it to finish the uci handshake
+    pub fn spawn(path: &str) -> io::Result<Self> {
+        let mut child = Command::new(path).stdin(Stdio::piped()).stdout(Stdio::piped()).spawn()?;
+        let stdin = child.stdin.take().unwrap();
+        let stdout = child.stdout.take().unwrap();
+
+        let (sender, lines) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+
+        let mut engine = ExternalEngine {
+            child,
+            stdin,
+            lines,
+            name: path.to_string(),
+            searching: false,
+            stale: 0,
+        };
+        engine.send("uci")?;
+        for line in engine.wait_for("uciok")? {
+            if let Some(name) = line.strip_prefix("id name ") {
+                engine.name = name.to_string();
+            }
+        }
+        engine.send("isready")?;
+        engine.wait_for("readyok")?;
+        Ok(engine)
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn is_searching(&self) -> bool {
+        self.searching
+    }
+
+    fn send(&mut self, command: &str) -> io::Result<()> {
+        writeln!(self.stdin, "{}", command)?;
+        self.stdin.flush()
+    }
+
+    // every line up to and including the one that is exactly reply
+    fn wait_for(&mut self, reply: &str) -> io::Result<Vec<String>> {
+        let deadline = Instant::now() + HANDSHAKE_TIMEOUT;
+        let mut lines = vec![];
+        loop {
+            let line = match self.lines.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                Ok(line) => line,
+                Err(RecvTimeoutError::Timeout) => {
+                    return Err(io::Error::new(io::ErrorKind::TimedOut, format!("no {} from the engine", reply)))
+                }
+                Err(RecvTimeoutError::Disconnected) => {
+                    return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "the engine exited"))
+                }
+            };
+            let done = line.trim() == reply;
+            lines.push(line);
+            if done {
+                return Ok(lines);
+            }
+        }
+    }
+
+    // starts a search of board, with no time it keeps going until stop
+    pub fn go(&mut self, board: &Board, movetime: Option<Duration>) -> io::Result<()> {
+        if self.searching {
+            self.stop()?;
+        }
+        self.send(&format!("position fen {}", board.to_fen()))?;
+        match movetime {
+            Some(t) => self.send(&format!("go movetime {}", t.as_millis()))?,
+            None => self.send("go infinite")?,
+        }
+        self.searching = true;
+        Ok(())
+    }
+
+    // the search's bestmove still comes back later but gets thrown away
+    pub fn stop(&mut self) -> io::Result<()> {
+        if self.searching {
+            self.send("stop")?;
+            self.searching = false;
+            self.stale += 1;
+        }
+        Ok(())
+    }
+
+    // everything the current search has said since the last poll, this never waits
+    // once the engine has exited and everything it said has been handed over this gives back an error
+    pub fn poll(&mut self) -> io::Result<Vec<EngineMessage>> {
+        let mut messages = vec![];
+        loop {
+            let line = match self.lines.try_recv() {
+                Ok(line) => line,
+                Err(TryRecvError::Empty) => return Ok(messages),
+                Err(TryRecvError::Disconnected) if !messages.is_empty() => return Ok(messages),
+                Err(TryRecvError::Disconnected) => {
+                    self.searching = false;
+                    return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "the engine exited"));
+                }
+            };
+            let message = match parse_line(&line) {
+                Some(message) => message,
+                None => continue,
+            };
+            if self.stale > 0 {
+                if let EngineMessage::BestMove(_) = message {
+                    self.stale -= 1;
+                }
+                continue;
+            }
+            if let EngineMessage::BestMove(_) = message {
+                self.searching = false;
+            }
+            messages.push(message);
+        }
+    }
+}
+
+// asks the engine to quit and makes sure it is gone
+impl Drop for ExternalEngine {
+    fn drop(&mut self) {
+        let _ = self.send("quit");
+        let deadline = Instant::now() + Duration::from_millis(500);
+        while Instant::now() < deadline {
+            if let Ok(Some(_)) = self.child.try_wait() {
+                return;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+// info and bestmove lines, everything else the engine says is ignored
+pub fn parse_line(line: &str) -> Option<EngineMessage> {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    match words.first() {
+        Some(&"bestmove") => Some(EngineMessage::BestMove(words.get(1).unwrap_or(&"0000").to_string())),
+        Some(&"info") if !words.contains(&"string") => Some(EngineMessage::Info(parse_info(&words[1..]))),
+        _ => None,
+    }
+}
+
+fn parse_info(words: &[&str]) -> Info {
+    let mut info = Info::default();
+    let mut i = 0;
+    while i < words.len() {
+        let next = words.get(i + 1);
+        match words[i] {
+            "depth" => info.depth = next.and_then(|v| v.parse().ok()),
+            "nodes" => info.nodes = next.and_then(|v| v.parse().ok()),
+            "score" => {
+                let value = words.get(i + 2).and_then(|v| v.parse().ok());
+                info.score = match next {
+                    Some(&"cp") => value.map(Score::Centipawns),
+                    Some(&"mate") => value.map(Score::Mate),
+                    _ => None,
+                };
+                i += 1;
+            }
+            // the pv is always last
+            "pv" => {
+                info.pv = words[i + 1..].iter().map(|m| m.to_string()).collect();
+                break;
+            }
+            _ => {
+                i += 1;
+                continue;
+            }
+        }
+        i += 2;
+    }
+    info
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_info_lines() {
+        let line = "info depth 18 seldepth 24 multipv 1 score cp -35 nodes 1234567 nps 900000 time 1371 pv e7e5 g1f3 b8c6";
+        assert_eq!(
+            parse_line(line),
+            Some(EngineMessage::Info(Info {
+                depth: Some(18),
+                score: Some(Score::Centipawns(-35)),
+                nodes: Some(1234567),
+                pv: vec!["e7e5".to_string(), "g1f3".to_string(), "b8c6".to_string()],
+            }))
+        );
+
+        let line = "info depth 5 score mate -2 lowerbound nodes 100";
+        match parse_line(line) {
+            Some(EngineMessage::Info(info)) => {
+                assert_eq!((info.depth, info.score, info.nodes), (Some(5), Some(Score::Mate(-2)), Some(100)))
+            }
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn parses_bestmove_and_ignores_the_rest() {
+        assert_eq!(parse_line("bestmove e7e8q ponder a2a1"), Some(EngineMessage::BestMove("e7e8q".to_string())));
+        assert_eq!(parse_line("info string NNUE evaluation enabled"), None);
+        assert_eq!(parse_line("readyok"), None);
+    }
+}
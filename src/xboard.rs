@@ -0,0 +1,368 @@
+// the chess engine communication protocol that xboard, winboard and older tournament scripts speak
+// commands come in on a reader thread and finished searches come back from the search thread, both through one
+// channel so the main loop only ever waits in one place
+use std::io::BufRead;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::board::*;
+use crate::engine::{
+    time_for_move, Engine, SearchLimits, SearchResult, DEFAULT_HASH_MB, DEFAULT_MOVES_TO_GO, MATE, MATE_BOUND, MAX_DEPTH,
+};
+
+// thinking time when neither st nor a clock has been given
+const DEFAULT_MOVE_TIME: Duration = Duration::from_secs(5);
+
+enum Event {
+    Command(String),
+    // the search with this id has finished
+    SearchDone(u64),
+}
+
+// the time control from level, base and increment in milliseconds
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct Level {
+    moves_per_session: u64,
+    base: u64,
+    increment: u64,
+}
+
+struct Xboard {
+    board: Board,
+    engine: Arc<Mutex<Engine>>,
+    stop: Arc<AtomicBool>,
+    events: Sender<Event>,
+    search: Option<JoinHandle<Option<Move>>>,
+    // bumped every time a search starts so the event from one that was thrown away can be told apart
+    search_id: u64,
+    // the color the engine plays, None while in force mode
+    engine_color: Option<Color>,
+    depth: u32,
+    move_time: Option<Duration>,
+    level: Option<Level>,
+    // the engine's own clock in milliseconds as last given by time
+    clock: Option<u64>,
+    post: bool,
+}
+
+// stdin is read on its own thread and fed into the same channel as finished searches
+pub fn run() {
+    let (events, receiver) = mpsc::channel();
+    let commands = events.clone();
+    thread::spawn(move || {
+        for line in std::io::stdin().lock().lines().map_while(Result::ok) {
+            if commands.send(Event::Command(line)).is_err() {
+                break;
+            }
+        }
+    });
+
+    let mut xboard = Xboard::new(events);
+    xboard.main_loop(receiver);
+    xboard.stop_search();
+}
+
+impl Xboard {
+    fn new(events: Sender<Event>) -> Self {
+        let engine = Engine::new(DEFAULT_HASH_MB);
+        Xboard {
+            board: Board::from_fen(START_FEN, 8).unwrap(),
+            stop: engine.stop_flag(),
+            engine: Arc::new(Mutex::new(engine)),
+            events,
+            search: None,
+            search_id: 0,
+            engine_color: Some(Color::Dark),
+            depth: MAX_DEPTH,
+            move_time: None,
+            level: None,
+            clock: None,
+            post: false,
+        }
+    }
+
+    fn main_loop(&mut self, receiver: Receiver<Event>) {
+        for event in receiver {
+            match event {
+                Event::Command(line) => {
+                    if !self.handle(&line) {
+                        return;
+                    }
+                }
+                Event::SearchDone(id) if id == self.search_id => self.finish_search(),
+                // a search that was stopped and thrown away
+                Event::SearchDone(_) => (),
+            }
+        }
+    }
+
+    // handles one command and gives back false once it is time to quit
+    fn handle(&mut self, line: &str) -> bool {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let args = words.get(1..).unwrap_or(&[]);
+        match words.first().copied() {
+            Some("protover") => {
+                println!("feature myname=\"chess\" usermove=1 setboard=1 ping=1 colors=0 sigint=0 sigterm=0 done=1");
+            }
+            Some("new") => {
+                self.stop_search();
+                self.engine.lock().unwrap().clear();
+                self.board = Board::from_fen(START_FEN, 8).unwrap();
+                self.engine_color = Some(Color::Dark);
+                self.depth = MAX_DEPTH;
+                self.move_time = None;
+                self.clock = None;
+            }
+            Some("force") => {
+                self.stop_search();
+                self.engine_color = None;
+            }
+            Some("go") => {
+                self.stop_search();
+                self.engine_color = Some(self.board.get_turn());
+                self.start_search();
+            }
+            Some("usermove") => match args.first() {
+                Some(mv) => self.user_move(mv),
+                None => println!("Error (no move given): usermove"),
+            },
+            Some("setboard") => {
+                self.stop_search();
+                match Board::from_fen(&args.join(" "), 8) {
+                    Ok(board) => self.board = board,
+                    Err(e) => println!("tellusererror Illegal position: {}", e),
+                }
+            }
+            Some("undo") => self.take_back(1),
+            Some("remove") => self.take_back(2),
+            Some("level") => match parse_level(args) {
+                Some(level) => {
+                    self.level = Some(level);
+                    self.move_time = None;
+                }
+                None => println!("Error (bad time control): {}", line),
+            },
+            Some("st") => match args.first().and_then(|s| s.parse().ok()) {
+                Some(seconds) => self.move_time = Some(Duration::from_secs(seconds)),
+                None => println!("Error (bad time): {}", line),
+            },
+            Some("sd") => match args.first().and_then(|s| s.parse::<u32>().ok()) {
+                Some(depth) => self.depth = depth.clamp(1, MAX_DEPTH),
+                None => println!("Error (bad depth): {}", line),
+            },
+            // centiseconds left on the engine's clock
+            Some("time") => self.clock = args.first().and_then(|s| s.parse::<u64>().ok()).map(|cs| cs * 10),
+            Some("result") => {
+                self.stop_search();
+                self.engine_color = None;
+            }
+            // move now
+            Some("?") => self.stop.store(true, Ordering::Relaxed),
+            Some("ping") => println!("pong {}", args.first().unwrap_or(&"")),
+            Some("post") => self.post = true,
+            Some("nopost") => self.post = false,
+            Some("quit") => return false,
+            // xboard, accepted, rejected, otim, easy, hard, computer, random and the rest need no answer
+            Some(_) | None => (),
+        }
+        true
+    }
+
+    fn user_move(&mut self, mv: &str) {
+        match self.board.move_from_string(mv) {
+            Some(m) => {
+                self.stop_search();
+                self.board.make_move(m);
+                if !self.report_result() && Some(self.board.get_turn()) == self.engine_color {
+                    self.start_search();
+                }
+            }
+            None => println!("Illegal move: {}", mv),
+        }
+    }
+
+    fn take_back(&mut self, moves: usize) {
+        self.stop_search();
+        for _ in 0..moves {
+            self.board.unmake_move();
+        }
+    }
+
+    fn limits(&self) -> SearchLimits {
+        // until the first time command the clock is still on the base time of the time control
+        let clock = self.clock.or(self.level.map(|l| l.base));
+        let time = match (self.move_time, clock, self.level) {
+            (Some(t), _, _) => t,
+            (None, Some(clock), level) => {
+                let (moves_per_session, increment) = level.map_or((0, 0), |l| (l.moves_per_session, l.increment));
+                // with a fixed number of moves per session only the ones left until the next session count
+                let moves_to_go = match moves_per_session {
+                    0 => DEFAULT_MOVES_TO_GO,
+                    mps => mps - (self.board.get_fullmove_number() as u64 - 1) % mps,
+                };
+                time_for_move(clock, increment, moves_to_go)
+            }
+            (None, None, _) => DEFAULT_MOVE_TIME,
+        };
+        SearchLimits {
+            depth: self.depth,
+            time: Some(time),
+        }
+    }
+
+    fn start_search(&mut self) {
+        self.search_id += 1;
+        self.stop.store(false, Ordering::Relaxed);
+
+        let id = self.search_id;
+        let limits = self.limits();
+        let board = self.board.clone();
+        let engine = self.engine.clone();
+        let events = self.events.clone();
+        let post = self.post;
+        self.search = Some(thread::spawn(move || {
+            let start = Instant::now();
+            let result = engine.lock().unwrap().search_with(&board, limits, |r| {
+                if post {
+                    println!("{}", thinking_line(&board, r, start.elapsed()));
+                }
+            });
+            let _ = events.send(Event::SearchDone(id));
+            result.best_move
+        }));
+    }
+
+    // stops the running search and throws its move away
+    fn stop_search(&mut self) {
+        if let Some(search) = self.search.take() {
+            self.stop.store(true, Ordering::Relaxed);
+            search.join().unwrap();
+        }
+    }
+
+    // plays the move the search found
+    fn finish_search(&mut self) {
+        let best_move = match self.search.take() {
+            Some(search) => search.join().unwrap(),
+            None => return,
+        };
+        if let Some(m) = best_move {
+            println!("move {}", self.board.move_to_string(m));
+            self.board.make_move(m);
+            self.report_result();
+        }
+    }
+
+    // tells the gui when the game has ended on the board and gives back whether it has
+    fn report_result(&self) -> bool {
+        let result = match self.board.game_status() {
+            GameStatus::Ongoing => return false,
+            GameStatus::Checkmate(Color::Light) => "1-0 {White mates}".to_string(),
+            GameStatus::Checkmate(Color::Dark) => "0-1 {Black mates}".to_string(),
+            GameStatus::Stalemate => "1/2-1/2 {Stalemate}".to_string(),
+            GameStatus::Draw(reason) => format!("1/2-1/2 {{Draw by {}}}", reason),
+        };
+        println!("{}", result);
+        true
+    }
+}
+
+// level <moves per session> <base> <increment> where base is minutes or minutes:seconds and increment is seconds
+fn parse_level(args: &[&str]) -> Option<Level> {
+    if args.len() != 3 {
+        return None;
+    }
+    let base = match args[1].split_once(':') {
+        Some((minutes, seconds)) => minutes.parse::<u64>().ok()? * 60 + seconds.parse::<u64>().ok()?,
+        None => args[1].parse::<u64>().ok()? * 60,
+    };
+    Some(Level {
+        moves_per_session: args[0].parse().ok()?,
+        base: base * 1000,
+        increment: (args[2].parse::<f64>().ok()? * 1000.0) as u64,
+    })
+}
+
+// ply, score, time in centiseconds, nodes and the principal variation, mates are 100000 plus the moves to mate
+fn thinking_line(board: &Board, result: &SearchResult, elapsed: Duration) -> String {
+    let score = match result.score.abs() >= MATE_BOUND {
+        true => result.score.signum() * (100_000 + (MATE - result.score.abs() + 1) / 2),
+        false => result.score,
+    };
+    let pv = result.best_move.map(|m| board.move_to_string(m)).unwrap_or_default();
+    format!("{} {} {} {} {}", result.depth, score, elapsed.as_millis() / 10, result.nodes, pv)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn xboard() -> Xboard {
+        Xboard::new(mpsc::channel().0)
+    }
+
+    #[test]
+    fn parses_time_controls() {
+        assert_eq!(
+            parse_level(&["40", "5", "0"]),
+            Some(Level { moves_per_session: 40, base: 300_000, increment: 0 })
+        );
+        assert_eq!(
+            parse_level(&["0", "2:30", "1.5"]),
+            Some(Level { moves_per_session: 0, base: 150_000, increment: 1500 })
+        );
+        assert_eq!(parse_level(&["0", "5"]), None);
+    }
+
+    #[test]
+    fn force_mode_moves_and_takebacks() {
+        let mut x = xboard();
+        x.handle("force");
+        x.handle("usermove e2e4");
+        x.handle("usermove e7e5");
+        x.handle("usermove e1e3");
+        assert_eq!(x.board.to_fen(), "rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq e6 0 2");
+        assert!(x.search.is_none());
+
+        x.handle("undo");
+        assert_eq!(x.board.to_fen(), "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1");
+        x.handle("setboard 7k/8/8/8/8/8/8/K6R w - - 0 1");
+        assert_eq!(x.board.to_fen(), "7k/8/8/8/8/8/8/K6R w - - 0 1");
+    }
+
+    #[test]
+    fn limits_follow_st_sd_and_the_clock() {
+        let mut x = xboard();
+        x.handle("sd 4");
+        x.handle("st 2");
+        assert_eq!(x.limits().depth, 4);
+        assert_eq!(x.limits().time, Some(Duration::from_secs(2)));
+
+        // 40 moves in 5 minutes with 100 seconds left and the full 40 moves to go
+        x.handle("level 40 5 0");
+        x.handle("time 10000");
+        assert_eq!(x.limits().time, Some(Duration::from_millis(2500)));
+    }
+
+    #[test]
+    fn plays_a_reply_after_the_users_move() {
+        let mut x = xboard();
+        let (events, receiver) = mpsc::channel();
+        x.events = events;
+        x.handle("new");
+        x.handle("sd 2");
+        x.handle("usermove e2e4");
+        assert!(x.search.is_some());
+
+        match receiver.recv().unwrap() {
+            Event::SearchDone(id) => assert_eq!(id, x.search_id),
+            Event::Command(_) => panic!("expected the search to finish"),
+        }
+        x.finish_search();
+        assert_eq!(x.board.get_turn(), Color::Light);
+        assert_eq!(x.board.last_move().map(|m| x.board.get_pieces()[m.to].unwrap().get_color()), Some(Color::Dark));
+    }
+}
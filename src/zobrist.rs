@@ -0,0 +1,80 @@
+// zobrist keys for hashing positions, every piece on every square, the side to move, each castling right
+// and each en passant file gets its own random number and a position's hash is all of its keys xored together
+use crate::board::*;
+
+const PIECE: u64 = 1;
+const SIDE: u64 = 2;
+const CASTLING: u64 = 3;
+const EN_PASSANT: u64 = 4;
+
+// splitmix64 so the keys are the same on every run without needing a random number crate,
+// working them out on the fly also means boards of any size get keys
+fn key(kind: u64, index: u64) -> u64 {
+    let mut z = (kind << 56 ^ index).wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+pub fn piece_key(pos: usize, piece: Piece) -> u64 {
+    key(PIECE, pos as u64 * 12 + piece.get_id() as u64)
+}
+
+// only xored in when dark is to move
+pub fn side_key() -> u64 {
+    key(SIDE, 0)
+}
+
+pub fn castling_key(rights: CastlingRights) -> u64 {
+    let mut ans = 0;
+    for (i, has) in [rights.light_king_side, rights.light_queen_side, rights.dark_king_side, rights.dark_queen_side].into_iter().enumerate() {
+        if has {
+            ans ^= key(CASTLING, i as u64);
+        }
+    }
+    ans
+}
+
+pub fn en_passant_key(file: usize) -> u64 {
+    key(EN_PASSANT, file as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // walks the whole move tree and checks the incremental hash against one built from scratch at every node
+    fn check_tree(b: &mut Board, depth: u32) {
+        assert_eq!(b.hash(), Board::from_fen(&b.to_fen(), 8).unwrap().hash(), "{}", b.to_fen());
+        if depth == 0 {
+            return;
+        }
+        for m in b.legal_moves() {
+            b.make_move(m);
+            check_tree(b, depth - 1);
+            b.unmake_move();
+        }
+    }
+
+    #[test]
+    fn incremental_hash_matches_fresh_hash() {
+        let mut b = Board::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1", 8).unwrap();
+        check_tree(&mut b, 2);
+        let mut b = Board::from_fen("rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8", 8).unwrap();
+        check_tree(&mut b, 2);
+    }
+
+    #[test]
+    fn transpositions_share_a_hash() {
+        let play = |moves: &[&str]| {
+            let mut b = Board::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", 8).unwrap();
+            for mv in moves {
+                let m = b.legal_moves().into_iter().find(|m| b.move_to_string(*m) == *mv).unwrap();
+                b.make_move(m);
+            }
+            b.hash()
+        };
+        assert_eq!(play(&["g1f3", "g8f6", "b1c3"]), play(&["b1c3", "g8f6", "g1f3"]));
+        assert_ne!(play(&["g1f3", "g8f6"]), play(&["g1f3"]));
+    }
+}